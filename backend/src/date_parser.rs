@@ -0,0 +1,415 @@
+//! Parse natural language dates, ie `tomorrow evening` or `next saturday`, into the range of
+//! time events are searched in.
+//!
+//! Phrases name a day or a range of days, optionally followed by a time of day:
+//!
+//! - `today`, `tonight`, `tomorrow`, `day after tomorrow`
+//! - `saturday`, `this saturday`, `next saturday`. `next` is the day in the following week
+//! - `this weekend`, `next weekend`, `this week`, `next week`, `this month`, `next month`
+//! - `next 3 days`, `in 2 days`
+//! - `december 1st`, `1 dec 2025`, `2024-12-01`
+//! - `morning`, `afternoon`, `evening` and `night` after a single day, ie `friday night`
+//!
+//! Ranges that already started begin at the current time.
+
+use crate::date_range::{end_of_day, local, start_of_day};
+use chrono::{DateTime, Datelike, Days, Months, NaiveDate, NaiveTime, Weekday};
+use chrono_tz::Tz;
+
+/// Words that don't change the meaning of a phrase
+const FILLER_WORDS: [&str; 4] = ["on", "the", "of", "at"];
+
+/// Start and end of the range of time `phrase` describes, with `now` in the time zone of the
+/// caller. Returns a description of the problem if the phrase is not understood
+pub fn parse_when(phrase: &str, now: DateTime<Tz>) -> Result<(DateTime<Tz>, DateTime<Tz>), String> {
+    let words = normalize(phrase);
+    let mut words: Vec<&str> = words.iter().map(String::as_str).collect();
+    let not_understood = || format!("`{phrase}` is not a date we understand");
+
+    let mut hours = None;
+    if let Some(last) = words.last() {
+        if let Some(part) = part_of_day(last) {
+            hours = Some(part);
+            words.pop();
+        }
+    }
+    // A time of day on its own, ie `tonight` or `this evening`, is today
+    if hours.is_some() && (words.is_empty() || words == ["this"]) {
+        words = vec!["today"];
+    }
+
+    let today = now.date_naive();
+    let (first_day, last_day) = parse_days(&words, today).ok_or_else(not_understood)?;
+    let tz = now.timezone();
+    let (start, end) = match hours {
+        Some(_) if first_day != last_day => {
+            return Err(format!(
+                "`{phrase}` has a time of day, which can only be used with a single day"
+            ))
+        }
+        Some((from, to)) => (
+            local(tz, first_day, hour(from)),
+            match to {
+                24 => end_of_day(tz, last_day),
+                to => local(tz, last_day, hour(to)) - chrono::Duration::seconds(1),
+            },
+        ),
+        None => (start_of_day(tz, first_day), end_of_day(tz, last_day)),
+    };
+    if end < now {
+        return Err(format!("`{phrase}` is in the past"));
+    }
+    return Ok((start.max(now), end));
+}
+
+/// Lower case words of `phrase`, without punctuation, ordinal suffixes or filler words
+fn normalize(phrase: &str) -> Vec<String> {
+    let words: Vec<String> = phrase
+        .to_lowercase()
+        .replace([',', '.'], " ")
+        .split_whitespace()
+        .map(|word| {
+            let is_ordinal = word.starts_with(|c: char| c.is_ascii_digit())
+                && ["st", "nd", "rd", "th"].iter().any(|s| word.ends_with(s));
+            if is_ordinal {
+                return word[..word.len() - 2].to_string();
+            }
+            return word.to_string();
+        })
+        .filter(|word| !FILLER_WORDS.contains(&word.as_str()))
+        .collect();
+    // `in` only matters before a number, ie `in 2 days`, not in `in the evening`
+    return words
+        .iter()
+        .enumerate()
+        .filter(|(i, word)| {
+            *word != "in" || words.get(i + 1).is_some_and(|next| number(next).is_some())
+        })
+        .map(|(_, word)| word.clone())
+        .collect();
+}
+
+/// First and last day of the days `words` describe, relative to `today`
+fn parse_days(words: &[&str], today: NaiveDate) -> Option<(NaiveDate, NaiveDate)> {
+    let single = |date: NaiveDate| Some((date, date));
+    let sunday = today + Days::new(6 - u64::from(today.weekday().num_days_from_monday()));
+    return match words {
+        ["today"] => single(today),
+        ["tomorrow"] => single(today + Days::new(1)),
+        ["day", "after", "tomorrow"] => single(today + Days::new(2)),
+        ["weekend"] | ["this", "weekend"] => Some((sunday - Days::new(1), sunday)),
+        ["next", "weekend"] => Some((sunday + Days::new(6), sunday + Days::new(7))),
+        ["this", "week"] => Some((today, sunday)),
+        ["next", "week"] => Some((sunday + Days::new(1), sunday + Days::new(7))),
+        ["this", "month"] => Some((today, last_day_of_month(today))),
+        ["next", "month"] => {
+            let first = today.with_day(1)? + Months::new(1);
+            Some((first, last_day_of_month(first)))
+        }
+        ["next", n, "days" | "day"] => {
+            Some((today, today.checked_add_days(Days::new(number(n)?))?))
+        }
+        ["in", n, "days" | "day"] => single(today.checked_add_days(Days::new(number(n)?))?),
+        ["in", n, "weeks" | "week"] => {
+            single(today.checked_add_days(Days::new(number(n)?.checked_mul(7)?))?)
+        }
+        [day] | ["this", day] if weekday(day).is_some() => {
+            let days_ahead = weekday(day)?.days_since(today.weekday());
+            single(today + Days::new(u64::from(days_ahead)))
+        }
+        ["next", day] => {
+            let monday = sunday + Days::new(1);
+            single(monday + Days::new(u64::from(weekday(day)?.num_days_from_monday())))
+        }
+        [date] => single(NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?),
+        [a, b] | [a, b, _] => {
+            let (month, day) = match (month(a), month(b)) {
+                (Some(month), None) => (month, b.parse().ok()?),
+                (None, Some(month)) => (month, a.parse().ok()?),
+                _ => return None,
+            };
+            let date = match words.get(2) {
+                Some(year) => NaiveDate::from_ymd_opt(year.parse().ok()?, month, day)?,
+                // Dates without a year are the next time that date comes around
+                None => {
+                    let date = NaiveDate::from_ymd_opt(today.year(), month, day)?;
+                    if date < today {
+                        NaiveDate::from_ymd_opt(today.year() + 1, month, day)?
+                    } else {
+                        date
+                    }
+                }
+            };
+            single(date)
+        }
+        _ => None,
+    };
+}
+
+/// Start and end hour of a time of day
+fn part_of_day(word: &str) -> Option<(u32, u32)> {
+    return match word {
+        "morning" => Some((6, 12)),
+        "afternoon" => Some((12, 17)),
+        "evening" => Some((17, 21)),
+        "night" => Some((21, 24)),
+        "tonight" => Some((17, 24)),
+        _ => None,
+    };
+}
+
+fn hour(hour: u32) -> NaiveTime {
+    return NaiveTime::from_hms_opt(hour, 0, 0).expect("Hours of the day are valid");
+}
+
+fn last_day_of_month(date: NaiveDate) -> NaiveDate {
+    let first = date.with_day(1).expect("Every month has a first day");
+    return first + Months::new(1) - Days::new(1);
+}
+
+/// A number written as digits or a word, ie `3` or `three`
+fn number(word: &str) -> Option<u64> {
+    const WORDS: [&str; 10] = [
+        "one", "two", "three", "four", "five", "six", "seven", "eight", "nine", "ten",
+    ];
+    if word == "a" {
+        return Some(1);
+    }
+    if let Some(i) = WORDS.iter().position(|w| *w == word) {
+        return Some(i as u64 + 1);
+    }
+    return word.parse().ok();
+}
+
+/// Day of the week named by `word`, ie `saturday` or `sat`
+pub fn weekday(word: &str) -> Option<Weekday> {
+    return match word {
+        "monday" | "mon" => Some(Weekday::Mon),
+        "tuesday" | "tue" | "tues" => Some(Weekday::Tue),
+        "wednesday" | "wed" => Some(Weekday::Wed),
+        "thursday" | "thu" | "thurs" => Some(Weekday::Thu),
+        "friday" | "fri" => Some(Weekday::Fri),
+        "saturday" | "sat" => Some(Weekday::Sat),
+        "sunday" | "sun" => Some(Weekday::Sun),
+        _ => None,
+    };
+}
+
+fn month(word: &str) -> Option<u32> {
+    const MONTHS: [&str; 12] = [
+        "january",
+        "february",
+        "march",
+        "april",
+        "may",
+        "june",
+        "july",
+        "august",
+        "september",
+        "october",
+        "november",
+        "december",
+    ];
+    let position = MONTHS
+        .iter()
+        .position(|month| *month == word || (word.len() >= 3 && month.starts_with(word)))?;
+    return Some(position as u32 + 1);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::date_range::parse_date;
+    use chrono_tz::America::Toronto;
+
+    /// Wednesday afternoon, the week before daylight saving time ends
+    const NOW: &str = "2024-10-30T14:30:00-04:00";
+
+    fn at(date: &str) -> DateTime<Tz> {
+        return parse_date(date).unwrap().with_timezone(&Toronto);
+    }
+
+    #[test]
+    /// Phrases are parsed into the range of time they describe
+    fn can_parse_phrases() {
+        let now = at(NOW);
+        let cases = [
+            ("today", NOW, "2024-10-30T23:59:59-04:00"),
+            (
+                "Tonight",
+                "2024-10-30T17:00:00-04:00",
+                "2024-10-30T23:59:59-04:00",
+            ),
+            (
+                "this evening",
+                "2024-10-30T17:00:00-04:00",
+                "2024-10-30T20:59:59-04:00",
+            ),
+            (
+                "tomorrow",
+                "2024-10-31T00:00:00-04:00",
+                "2024-10-31T23:59:59-04:00",
+            ),
+            (
+                "tomorrow evening",
+                "2024-10-31T17:00:00-04:00",
+                "2024-10-31T20:59:59-04:00",
+            ),
+            (
+                "tomorrow morning",
+                "2024-10-31T06:00:00-04:00",
+                "2024-10-31T11:59:59-04:00",
+            ),
+            (
+                "day after tomorrow",
+                "2024-11-01T00:00:00-04:00",
+                "2024-11-01T23:59:59-04:00",
+            ),
+            (
+                "friday night",
+                "2024-11-01T21:00:00-04:00",
+                "2024-11-01T23:59:59-04:00",
+            ),
+            (
+                "Saturday",
+                "2024-11-02T00:00:00-04:00",
+                "2024-11-02T23:59:59-04:00",
+            ),
+            (
+                "on Saturday",
+                "2024-11-02T00:00:00-04:00",
+                "2024-11-02T23:59:59-04:00",
+            ),
+            (
+                "this sat",
+                "2024-11-02T00:00:00-04:00",
+                "2024-11-02T23:59:59-04:00",
+            ),
+            (
+                "next Saturday",
+                "2024-11-09T00:00:00-05:00",
+                "2024-11-09T23:59:59-05:00",
+            ),
+            (
+                "sunday in the afternoon",
+                "2024-11-03T12:00:00-05:00",
+                "2024-11-03T16:59:59-05:00",
+            ),
+            ("wednesday", NOW, "2024-10-30T23:59:59-04:00"),
+            (
+                "next wednesday",
+                "2024-11-06T00:00:00-05:00",
+                "2024-11-06T23:59:59-05:00",
+            ),
+            (
+                "monday",
+                "2024-11-04T00:00:00-05:00",
+                "2024-11-04T23:59:59-05:00",
+            ),
+            (
+                "this weekend",
+                "2024-11-02T00:00:00-04:00",
+                "2024-11-03T23:59:59-05:00",
+            ),
+            (
+                "next weekend",
+                "2024-11-09T00:00:00-05:00",
+                "2024-11-10T23:59:59-05:00",
+            ),
+            ("this week", NOW, "2024-11-03T23:59:59-05:00"),
+            (
+                "next week",
+                "2024-11-04T00:00:00-05:00",
+                "2024-11-10T23:59:59-05:00",
+            ),
+            ("this month", NOW, "2024-10-31T23:59:59-04:00"),
+            (
+                "next month",
+                "2024-11-01T00:00:00-04:00",
+                "2024-11-30T23:59:59-05:00",
+            ),
+            ("next 3 days", NOW, "2024-11-02T23:59:59-04:00"),
+            ("next seven days", NOW, "2024-11-06T23:59:59-05:00"),
+            (
+                "in 2 days",
+                "2024-11-01T00:00:00-04:00",
+                "2024-11-01T23:59:59-04:00",
+            ),
+            (
+                "in a week",
+                "2024-11-06T00:00:00-05:00",
+                "2024-11-06T23:59:59-05:00",
+            ),
+            (
+                "December 1st",
+                "2024-12-01T00:00:00-05:00",
+                "2024-12-01T23:59:59-05:00",
+            ),
+            (
+                "1 dec",
+                "2024-12-01T00:00:00-05:00",
+                "2024-12-01T23:59:59-05:00",
+            ),
+            (
+                "Dec 1, 2025",
+                "2025-12-01T00:00:00-05:00",
+                "2025-12-01T23:59:59-05:00",
+            ),
+            (
+                "october 1",
+                "2025-10-01T00:00:00-04:00",
+                "2025-10-01T23:59:59-04:00",
+            ),
+            (
+                "2024-11-15",
+                "2024-11-15T00:00:00-05:00",
+                "2024-11-15T23:59:59-05:00",
+            ),
+            (
+                "2024-11-15 evening",
+                "2024-11-15T17:00:00-05:00",
+                "2024-11-15T20:59:59-05:00",
+            ),
+        ];
+        for (phrase, start, end) in cases {
+            assert_eq!(
+                parse_when(phrase, now),
+                Ok((at(start), at(end))),
+                "Parsing `{phrase}`"
+            );
+        }
+    }
+
+    #[test]
+    /// Phrases that can't be searched are rejected
+    fn can_reject_phrases() {
+        let now = at(NOW);
+        let cases = [
+            ("", "`` is not a date we understand"),
+            ("someday", "`someday` is not a date we understand"),
+            ("february 30", "`february 30` is not a date we understand"),
+            ("next fortnight", "`next fortnight` is not a date we understand"),
+            (
+                "next 99999999999 days",
+                "`next 99999999999 days` is not a date we understand",
+            ),
+            (
+                "in 9999999999999999999 weeks",
+                "`in 9999999999999999999 weeks` is not a date we understand",
+            ),
+            ("this morning", "`this morning` is in the past"),
+            ("2024-10-01", "`2024-10-01` is in the past"),
+            (
+                "this weekend evening",
+                "`this weekend evening` has a time of day, which can only be used with a single day",
+            ),
+        ];
+        for (phrase, error) in cases {
+            assert_eq!(
+                parse_when(phrase, now),
+                Err(error.to_string()),
+                "Parsing `{phrase}`"
+            );
+        }
+    }
+}
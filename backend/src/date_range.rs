@@ -0,0 +1,177 @@
+//! Date ranges events are searched in
+
+use chrono::{
+    DateTime, Datelike, Days, Duration, FixedOffset, NaiveDate, NaiveTime, TimeZone, Weekday,
+};
+use chrono_tz::{America::New_York, Tz};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Number of minutes in a day
+const MINUTES_PER_DAY: usize = 24 * 60;
+
+/// Dates events are searched between. Ends that are not set are left open
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct DateBounds {
+    pub start: Option<DateTime<FixedOffset>>,
+    pub end: Option<DateTime<FixedOffset>>,
+}
+
+/// Date ranges relative to the current time
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DateShortcut {
+    /// From now until the end of today
+    Today,
+    /// Saturday and Sunday of this week. From now until the end of Sunday during the weekend
+    ThisWeekend,
+    /// From now until the end of the 7th day from today
+    #[serde(rename = "next_7_days")]
+    Next7Days,
+}
+
+impl DateShortcut {
+    /// Start and end of this range, with `now` in the time zone of the caller
+    pub fn resolve(&self, now: DateTime<Tz>) -> (DateTime<Tz>, DateTime<Tz>) {
+        let today = now.date_naive();
+        return match self {
+            DateShortcut::Today => (now, end_of_day(now.timezone(), today)),
+            DateShortcut::ThisWeekend => {
+                let sunday =
+                    today + Days::new(6 - u64::from(today.weekday().num_days_from_monday()));
+                let saturday = sunday - Days::new(1);
+                let start = match today.weekday() {
+                    Weekday::Sat | Weekday::Sun => now,
+                    _ => start_of_day(now.timezone(), saturday),
+                };
+                (start, end_of_day(now.timezone(), sunday))
+            }
+            DateShortcut::Next7Days => (now, end_of_day(now.timezone(), today + Days::new(7))),
+        };
+    }
+}
+
+/// Parse the IANA time zone of a caller, ie `America/Vancouver`. Defaults to `America/New_York`
+pub fn parse_timezone(timezone: Option<&str>) -> Result<Tz, String> {
+    return match timezone {
+        Some(timezone) => timezone
+            .parse()
+            .map_err(|_| format!("`timezone` `{timezone}` is not a valid time zone")),
+        None => Ok(New_York),
+    };
+}
+
+/// First moment of `date` in `tz`
+pub fn start_of_day(tz: Tz, date: NaiveDate) -> DateTime<Tz> {
+    return local(tz, date, NaiveTime::MIN);
+}
+
+/// Last second of `date` in `tz`
+pub fn end_of_day(tz: Tz, date: NaiveDate) -> DateTime<Tz> {
+    return local(
+        tz,
+        date,
+        NaiveTime::from_hms_opt(23, 59, 59).expect("Valid time"),
+    );
+}
+
+/// `time` on `date` in `tz`. Times skipped by a daylight saving time change are moved to the
+/// first minute after the change
+pub fn local(tz: Tz, date: NaiveDate, time: NaiveTime) -> DateTime<Tz> {
+    let naive = date.and_time(time);
+    // Changes skip less than a day, even Samoa skipping 2011-12-30
+    let mut next = naive;
+    for _ in 0..=MINUTES_PER_DAY {
+        if let Some(local) = tz.from_local_datetime(&next).earliest() {
+            return local;
+        }
+        next += Duration::minutes(1);
+    }
+    return tz.from_utc_datetime(&naive);
+}
+
+/// Parse a date in RFC 3339. Meetup also accepts a time zone name after the date, ie
+/// `2024-12-01T00:00:00-05:00[US/Eastern]`, which is ignored
+pub fn parse_date(date: &str) -> Option<DateTime<FixedOffset>> {
+    let date = date.split('[').next().unwrap_or_default();
+    return DateTime::parse_from_rfc3339(date).ok();
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono_tz::America::{Santiago, Toronto};
+
+    fn at(date: &str) -> DateTime<Tz> {
+        return parse_date(date).unwrap().with_timezone(&Toronto);
+    }
+
+    #[test]
+    /// Shortcuts are resolved in the time zone of the caller
+    fn can_resolve_shortcuts() {
+        // Wednesday
+        let now = at("2024-11-20T09:30:00-05:00");
+        assert_eq!(
+            DateShortcut::Today.resolve(now),
+            (now, at("2024-11-20T23:59:59-05:00"))
+        );
+        assert_eq!(
+            DateShortcut::ThisWeekend.resolve(now),
+            (
+                at("2024-11-23T00:00:00-05:00"),
+                at("2024-11-24T23:59:59-05:00")
+            )
+        );
+        assert_eq!(
+            DateShortcut::Next7Days.resolve(now),
+            (now, at("2024-11-27T23:59:59-05:00"))
+        );
+
+        // Sunday, in UTC it is already Monday
+        let now = at("2024-11-24T21:00:00-05:00");
+        assert_eq!(
+            DateShortcut::ThisWeekend.resolve(now),
+            (now, at("2024-11-24T23:59:59-05:00"))
+        );
+    }
+
+    #[test]
+    /// Days are ended in their own offset across daylight saving time changes
+    fn can_cross_daylight_saving_time() {
+        let now = at("2024-10-31T12:00:00-04:00");
+        assert_eq!(
+            DateShortcut::Next7Days.resolve(now).1,
+            at("2024-11-07T23:59:59-05:00")
+        );
+    }
+
+    #[test]
+    /// Times skipped by a daylight saving time change resolve to the end of the change, even
+    /// when the change skips midnight
+    fn can_skip_daylight_saving_time_gaps() {
+        let date = |date: &str| NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap();
+        // Clocks in Santiago go from 00:00 to 01:00
+        assert_eq!(
+            start_of_day(Santiago, date("2024-09-08")),
+            parse_date("2024-09-08T01:00:00-03:00").unwrap()
+        );
+        assert_eq!(
+            local(
+                Toronto,
+                date("2024-03-10"),
+                NaiveTime::from_hms_opt(2, 30, 0).unwrap()
+            ),
+            at("2024-03-10T03:00:00-04:00")
+        );
+    }
+
+    #[test]
+    /// Dates are parsed as RFC 3339, ignoring a time zone name after the date
+    fn can_parse_date() {
+        assert_eq!(
+            parse_date("2024-12-01T00:00:00-05:00[US/Eastern]"),
+            Some(at("2024-12-01T00:00:00-05:00").fixed_offset())
+        );
+        assert_eq!(parse_date("tomorrow"), None);
+    }
+}
@@ -0,0 +1,467 @@
+//! Types accepted and returned by the versioned FreshMeet API.
+//!
+//! Unlike the Meetup types in `meetup::query::request::gql2` and the request types of the
+//! unversioned routes, these only change with a new API version. The contract tests below fail if
+//! their JSON or schema changes, in which case either the change is undone, or a new API version
+//! is added
+
+use crate::date_range::DateShortcut;
+use crate::map_link::TravelMode;
+use crate::meetup::query::common::SortField;
+use crate::meetup::query::request::gql2::{DedupMode, GQLResponse, Group, Node, SeriesMode, Venue};
+use crate::routes::meetup::{EventTypeFilter, RecommendedMeetupsQueryParams, SearchRequestBody};
+use crate::utils::deserialize_list;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use utoipa::{IntoParams, ToSchema};
+
+/// Query parameters for recommended events
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema, IntoParams)]
+#[serde(rename_all = "camelCase")]
+#[into_params(parameter_in = Query)]
+pub struct RecommendedEventsQueryDto {
+    /// Only return events starting on or after this day
+    pub start_date: DateTime<Utc>,
+    /// Only return events starting on or before this day
+    pub end_date: DateTime<Utc>,
+    /// How events that are part of a series are returned. Defaults to `nested`
+    pub series_mode: Option<SeriesMode>,
+    /// Number of upcoming events to request for each series. Defaults to 5
+    pub series_events: Option<i32>,
+    /// How duplicated events are detected. Defaults to `id`
+    pub dedup: Option<DedupMode>,
+    /// Cursor of the page to get, the `nextCursor` of the previous page
+    pub after: Option<String>,
+    /// Latitude to compute the distance to events from
+    pub from_lat: Option<f64>,
+    /// Longitude to compute the distance to events from
+    pub from_lon: Option<f64>,
+    /// Only return in person events at most this many kilometres away. Requires `fromLat` and
+    /// `fromLon`. Online events are always returned
+    pub max_distance_km: Option<f64>,
+    /// Sort events by distance, closest first. Requires `fromLat` and `fromLon`
+    pub sort_by_distance: Option<bool>,
+    /// Comma separated travel modes to estimate travel times to events for, ie `walking,transit`.
+    /// Requires `fromLat` and `fromLon`
+    pub travel_modes: Option<String>,
+    /// Field Meetup sorts events by. Events are sorted by start time if not set
+    pub sort_field: Option<SortField>,
+    /// Types of events to return. Defaults to `physical`
+    pub event_type: Option<EventTypeFilter>,
+    /// Only return events in this topic category
+    pub category_id: Option<String>,
+    /// Comma separated days of the week events start on, ie `sat,sun` or `weekdays`
+    pub days_of_week: Option<String>,
+    /// Only return events starting at or after this time of day, ie `18:00`
+    pub time_from: Option<String>,
+    /// Only return events starting at or before this time of day, ie `21:30`
+    pub time_to: Option<String>,
+}
+
+/// Parameters of an event search
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct SearchEventsBodyDto {
+    /// Search query
+    pub query: Option<String>,
+    /// Only return events starting after this date, in RFC 3339. Defaults to now
+    pub start_date: Option<String>,
+    /// Only return events starting before this date, in RFC 3339
+    pub end_date: Option<String>,
+    /// Search in a range of dates relative to now, instead of `start_date` and `end_date`
+    pub date_range: Option<DateShortcut>,
+    /// Dates to search in written in English, ie `tomorrow evening`
+    pub when: Option<String>,
+    /// IANA time zone `date_range` and `when` are resolved in. Defaults to `America/New_York`
+    pub timezone: Option<String>,
+    /// Events to return per page
+    pub per_page: Option<u32>,
+    /// Latitude to search around. Defaults to Toronto
+    pub lat: Option<f64>,
+    /// Longitude to search around. Defaults to Toronto
+    pub lon: Option<f64>,
+    /// City to search in. Defaults to Toronto
+    pub city: Option<String>,
+    /// How events that are part of a series are returned. Defaults to `nested`
+    pub series_mode: Option<SeriesMode>,
+    /// Number of upcoming events to request for each series. Defaults to 5
+    pub series_events: Option<i32>,
+    /// How duplicated events are detected. Defaults to `id`
+    pub dedup: Option<DedupMode>,
+    /// Cursor of the page to get, the `nextCursor` of the previous page
+    pub after: Option<String>,
+    /// Latitude to compute the distance to events from
+    pub from_lat: Option<f64>,
+    /// Longitude to compute the distance to events from
+    pub from_lon: Option<f64>,
+    /// Only return in person events at most this many kilometres away. Requires `from_lat` and
+    /// `from_lon`. Online events are always returned
+    pub max_distance_km: Option<f64>,
+    /// Sort events by distance, closest first. Requires `from_lat` and `from_lon`
+    pub sort_by_distance: Option<bool>,
+    /// Travel modes to estimate travel times to events for, as a list or a comma separated
+    /// string. Requires `from_lat` and `from_lon`
+    #[serde(default, deserialize_with = "deserialize_list")]
+    pub travel_modes: Option<Vec<TravelMode>>,
+    /// Field Meetup sorts events by. Events are sorted by start time if not set
+    pub sort_field: Option<SortField>,
+    /// Types of events to return. Defaults to `physical`
+    pub event_type: Option<EventTypeFilter>,
+    /// Only return events in this topic category
+    pub category_id: Option<String>,
+    /// Days of the week events start on, ie `["sat", "sun"]`, `"sat,sun"` or `["weekdays"]`
+    #[serde(default, deserialize_with = "deserialize_list")]
+    pub days_of_week: Option<Vec<String>>,
+    /// Only return events starting at or after this time of day, ie `18:00`
+    pub time_from: Option<String>,
+    /// Only return events starting at or before this time of day, ie `21:30`
+    pub time_to: Option<String>,
+}
+
+/// A page of events
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct EventsDto {
+    pub events: Vec<EventDto>,
+    /// Total number of events found
+    pub total_count: i64,
+    /// Cursor to pass as `after` to get the next page. Not set on the last page
+    pub next_cursor: Option<String>,
+}
+
+/// An event
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct EventDto {
+    pub id: String,
+    pub title: String,
+    /// Meetup page of the event
+    pub url: String,
+    /// Start time in RFC 3339
+    pub start_time: String,
+    /// End time in RFC 3339
+    pub end_time: Option<String>,
+    /// Sanitized HTML description
+    pub description: String,
+    pub is_online: bool,
+    pub is_attending: bool,
+    pub is_saved: bool,
+    /// Number of people going
+    pub rsvp_count: i64,
+    /// Currency of the event fee, if there is one
+    pub fee_currency: Option<String>,
+    pub image_url: Option<String>,
+    pub group: GroupDto,
+    /// Other groups the event is cross posted by
+    pub cross_posted_groups: Vec<GroupDto>,
+    /// Not set for online events
+    pub venue: Option<VenueDto>,
+    /// Link to directions to the venue
+    pub map_url: Option<String>,
+    /// Distance to the venue in kilometres, when searching from a location
+    pub distance_km: Option<f64>,
+    /// Estimated minutes to travel to the venue by each travel mode
+    pub travel_minutes: BTreeMap<TravelMode, u32>,
+    /// Search queries the event matched
+    pub matched_queries: Vec<String>,
+}
+
+/// A group hosting events
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupDto {
+    pub id: String,
+    pub name: String,
+    /// Name of the group in Meetup URLs
+    pub urlname: String,
+    /// IANA time zone of the group
+    pub timezone: String,
+}
+
+/// Where an event takes place
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct VenueDto {
+    pub name: String,
+    /// Full address, formatted by the conventions of the country of the venue
+    pub address: Option<String>,
+    pub city: String,
+    pub state: String,
+    /// ISO 3166 country code
+    pub country: String,
+    pub lat: f64,
+    pub lon: f64,
+}
+
+impl From<RecommendedEventsQueryDto> for RecommendedMeetupsQueryParams {
+    fn from(query: RecommendedEventsQueryDto) -> Self {
+        return Self {
+            start_date: query.start_date,
+            end_date: query.end_date,
+            series_mode: query.series_mode,
+            series_events: query.series_events,
+            dedup: query.dedup,
+            after: query.after,
+            from_lat: query.from_lat,
+            from_lon: query.from_lon,
+            max_distance_km: query.max_distance_km,
+            sort_by_distance: query.sort_by_distance,
+            travel_modes: query.travel_modes,
+            sort_field: query.sort_field,
+            event_type: query.event_type,
+            category_id: query.category_id,
+            days_of_week: query.days_of_week,
+            time_from: query.time_from,
+            time_to: query.time_to,
+        };
+    }
+}
+
+impl From<SearchEventsBodyDto> for SearchRequestBody {
+    fn from(body: SearchEventsBodyDto) -> Self {
+        return Self {
+            query: body.query,
+            start_date: body.start_date,
+            end_date: body.end_date,
+            date_range: body.date_range,
+            when: body.when,
+            timezone: body.timezone,
+            per_page: body.per_page,
+            lat: body.lat,
+            lon: body.lon,
+            city: body.city,
+            series_mode: body.series_mode,
+            series_events: body.series_events,
+            dedup: body.dedup,
+            after: body.after,
+            from_lat: body.from_lat,
+            from_lon: body.from_lon,
+            max_distance_km: body.max_distance_km,
+            sort_by_distance: body.sort_by_distance,
+            travel_modes: body.travel_modes,
+            sort_field: body.sort_field,
+            event_type: body.event_type,
+            category_id: body.category_id,
+            days_of_week: body.days_of_week,
+            time_from: body.time_from,
+            time_to: body.time_to,
+        };
+    }
+}
+
+impl From<GQLResponse> for EventsDto {
+    fn from(res: GQLResponse) -> Self {
+        let result = res.data.unwrap_or_default().result;
+        return Self {
+            events: result
+                .edges
+                .into_iter()
+                .map(|e| EventDto::from(e.node))
+                .collect(),
+            total_count: result.total_count,
+            next_cursor: result
+                .page_info
+                .end_cursor
+                .filter(|_| result.page_info.has_next_page),
+        };
+    }
+}
+
+impl From<Node> for EventDto {
+    /// Convert an event. The description is used as is, so it must already be sanitized HTML
+    fn from(node: Node) -> Self {
+        return Self {
+            venue: node.venue.filter(|_| !node.is_online).map(VenueDto::from),
+            id: node.id,
+            title: node.title,
+            url: node.event_url,
+            start_time: node.date_time,
+            end_time: node.end_time,
+            description: node.description,
+            is_online: node.is_online,
+            is_attending: node.is_attending,
+            is_saved: node.is_saved,
+            rsvp_count: node.rsvps.total_count,
+            fee_currency: node.fee_settings.map(|f| f.currency),
+            image_url: node.featured_event_photo.map(|p| p.high_res_url),
+            group: GroupDto::from(node.group),
+            cross_posted_groups: node
+                .cross_posted_groups
+                .unwrap_or_default()
+                .into_iter()
+                .map(GroupDto::from)
+                .collect(),
+            map_url: node.map_url,
+            distance_km: node.distance_km,
+            travel_minutes: node.travel_minutes.unwrap_or_default(),
+            matched_queries: node.matched_queries.unwrap_or_default(),
+        };
+    }
+}
+
+impl From<Group> for GroupDto {
+    fn from(group: Group) -> Self {
+        return Self {
+            id: group.id,
+            name: group.name,
+            urlname: group.urlname,
+            timezone: group.timezone,
+        };
+    }
+}
+
+impl From<Venue> for VenueDto {
+    fn from(venue: Venue) -> Self {
+        return Self {
+            name: venue.name,
+            address: venue.formatted_address,
+            city: venue.city,
+            state: venue.state,
+            country: venue.country,
+            lat: venue.lat,
+            lon: venue.lon,
+        };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::meetup::query::request::gql2::{
+        Edge, FeaturedEventPhoto, FeeSettings, GQLData, MeetupResult, PageInfo, Rsvps,
+    };
+    use serde_json::Value;
+    use utoipa::openapi::{schema::Schema, RefOr};
+    use utoipa::PartialSchema;
+
+    /// Compare `actual` to the contract in `contracts/v1/{name}.json`. Set `UPDATE_CONTRACTS=1`
+    /// to write the contract instead, after deciding the change is intended
+    fn assert_contract(name: &str, actual: Value) {
+        let path = format!("{}/contracts/v1/{name}.json", env!("CARGO_MANIFEST_DIR"));
+        if std::env::var("UPDATE_CONTRACTS").is_ok_and(|v| v == "1") {
+            std::fs::write(&path, serde_json::to_string_pretty(&actual).unwrap() + "\n").unwrap();
+        }
+        let contract: Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(
+            actual, contract,
+            "{name} no longer matches the v1 API contract in {path}. Breaking changes need a new API version"
+        );
+    }
+
+    fn response() -> GQLResponse {
+        let group = |id: &str| Group {
+            id: id.into(),
+            name: format!("Group {id}"),
+            urlname: format!("group-{id}"),
+            timezone: "America/Toronto".into(),
+            ..Default::default()
+        };
+        return GQLResponse {
+            data: Some(GQLData {
+                result: MeetupResult {
+                    page_info: PageInfo {
+                        has_next_page: true,
+                        end_cursor: Some("MjA=".into()),
+                    },
+                    total_count: 21,
+                    edges: vec![Edge {
+                        node: Node {
+                            id: "304823122".into(),
+                            title: "Rust Toronto".into(),
+                            event_url: "https://www.meetup.com/group-1/events/304823122/".into(),
+                            date_time: "2024-12-01T18:00:00-05:00".into(),
+                            end_time: Some("2024-12-01T20:00:00-05:00".into()),
+                            description: "<p>Talks</p>".into(),
+                            is_attending: true,
+                            rsvps: Rsvps { total_count: 42 },
+                            fee_settings: Some(FeeSettings {
+                                accepts: "CARD".into(),
+                                currency: "CAD".into(),
+                            }),
+                            featured_event_photo: Some(FeaturedEventPhoto {
+                                high_res_url: "https://secure.meetupstatic.com/photo.jpeg".into(),
+                                ..Default::default()
+                            }),
+                            group: group("1"),
+                            cross_posted_groups: Some(vec![group("2")]),
+                            venue: Some(Venue {
+                                name: "Union Station".into(),
+                                formatted_address: Some(
+                                    "65 Front St W, Toronto, ON M5J 1E6".into(),
+                                ),
+                                city: "Toronto".into(),
+                                state: "ON".into(),
+                                country: "ca".into(),
+                                lat: 43.6453,
+                                lon: -79.3806,
+                                ..Default::default()
+                            }),
+                            map_url: Some("geo:43.6453,-79.3806".into()),
+                            distance_km: Some(1.5),
+                            travel_minutes: Some(BTreeMap::from([(TravelMode::Walking, 20)])),
+                            matched_queries: Some(vec!["rust".into()]),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    }],
+                },
+            }),
+            ..Default::default()
+        };
+    }
+
+    #[test]
+    /// Events are serialized as agreed in the v1 contract
+    fn events_match_contract() {
+        let events = EventsDto::from(response());
+        assert_contract("events", serde_json::to_value(events).unwrap());
+    }
+
+    #[test]
+    /// The OpenAPI schema of requests and events is as agreed in the v1 contract
+    fn schema_matches_contract() {
+        let schemas: BTreeMap<&str, RefOr<Schema>> = BTreeMap::from([
+            (
+                "RecommendedEventsQueryDto",
+                RecommendedEventsQueryDto::schema(),
+            ),
+            ("SearchEventsBodyDto", SearchEventsBodyDto::schema()),
+            ("EventsDto", EventsDto::schema()),
+            ("EventDto", EventDto::schema()),
+            ("GroupDto", GroupDto::schema()),
+            ("VenueDto", VenueDto::schema()),
+            // Enums in requests, which are shared with the unversioned routes
+            ("DateShortcut", DateShortcut::schema()),
+            ("DedupMode", DedupMode::schema()),
+            ("EventTypeFilter", EventTypeFilter::schema()),
+            ("SeriesMode", SeriesMode::schema()),
+            ("SortField", SortField::schema()),
+            ("TravelMode", TravelMode::schema()),
+        ]);
+        let mut schemas = serde_json::to_value(schemas).unwrap();
+        // Documentation can change without breaking clients
+        strip_descriptions(&mut schemas);
+        assert_contract("schema", schemas);
+    }
+
+    /// Remove the description of every schema in `value`
+    fn strip_descriptions(value: &mut Value) {
+        match value {
+            Value::Object(object) => {
+                object.remove("description");
+                for (key, value) in object.iter_mut() {
+                    // Property names are not descriptions, even if a property is named `description`
+                    match (key.as_str(), value) {
+                        ("properties", Value::Object(properties)) => {
+                            properties.values_mut().for_each(strip_descriptions)
+                        }
+                        (_, value) => strip_descriptions(value),
+                    }
+                }
+            }
+            Value::Array(items) => items.iter_mut().for_each(strip_descriptions),
+            _ => {}
+        }
+    }
+}
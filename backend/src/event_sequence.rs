@@ -0,0 +1,230 @@
+//! Revisions of calendar events, persisted to a TOML file on disk
+//!
+//! Calendar apps only update an event they have already seen if its `SEQUENCE` increased, so the
+//! sequence of an event is bumped every time its time changes. Feeds use the time of the last
+//! change as the `updated` time of an event.
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use tokio::sync::RwLock;
+use tracing::info;
+
+/// Maximum number of event revisions remembered. Revisions of the events that were seen the
+/// longest time ago are forgotten first
+const MAX_EVENTS: usize = 10_000;
+
+/// How often the time an event was last seen is updated, so looking up events that didn't
+/// change doesn't write to disk every time
+const SEEN_INTERVAL_HOURS: i64 = 1;
+
+/// Last seen revision of an event
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct EventRevision {
+    start: String,
+    end: String,
+    sequence: u32,
+    /// When this revision was first seen
+    #[serde(default = "Utc::now")]
+    updated: DateTime<Utc>,
+    /// When the event was last seen, to the nearest [SEEN_INTERVAL_HOURS]
+    #[serde(default = "Utc::now")]
+    seen: DateTime<Utc>,
+}
+
+/// Revision of an event
+#[derive(Debug, Clone, PartialEq)]
+pub struct Revision {
+    /// Number of times the event changed
+    pub sequence: u32,
+    /// When the event last changed
+    pub updated: DateTime<Utc>,
+}
+
+/// Layout of the event sequences file
+#[derive(Serialize, Deserialize, Default, Clone)]
+struct EventSequenceFile {
+    /// Revisions of events, by event UID
+    #[serde(default)]
+    events: BTreeMap<String, EventRevision>,
+}
+
+/// Keeps track of the sequence of calendar events, writing every change to disk
+pub struct EventSequenceStore {
+    path: PathBuf,
+    file: RwLock<EventSequenceFile>,
+}
+
+impl EventSequenceStore {
+    /// Load event sequences from the file at `path`. If the file does not exist, start with no
+    /// events
+    pub fn load(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let file = match std::fs::read_to_string(&path) {
+            Ok(contents) => toml::from_str::<EventSequenceFile>(&contents).map_err(|e| {
+                anyhow!(
+                    "Failed to parse event sequences in {}: {}",
+                    path.display(),
+                    e
+                )
+            })?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => EventSequenceFile::default(),
+            Err(e) => {
+                return Err(anyhow!(
+                    "Failed to read event sequences in {}: {}",
+                    path.display(),
+                    e
+                ))
+            }
+        };
+        info!(
+            "Loaded {} event sequences from {}",
+            file.events.len(),
+            path.display()
+        );
+        return Ok(Self {
+            path,
+            file: RwLock::new(file),
+        });
+    }
+
+    /// Get the revision of each event in `events`, given as `(uid, start, end)`. A new revision
+    /// is created if the start or end of an event changed since it was last seen
+    pub async fn revisions(&self, events: &[(String, String, String)]) -> Result<Vec<Revision>> {
+        let now = Utc::now();
+        let mut file = self.file.write().await;
+        let mut changed = false;
+        let mut revisions = Vec::with_capacity(events.len());
+        for (uid, start, end) in events {
+            let revision = file.events.entry(uid.clone()).or_insert_with(|| {
+                changed = true;
+                EventRevision {
+                    start: start.clone(),
+                    end: end.clone(),
+                    sequence: 0,
+                    updated: now,
+                    seen: now,
+                }
+            });
+            if revision.start != *start || revision.end != *end {
+                revision.start = start.clone();
+                revision.end = end.clone();
+                revision.sequence += 1;
+                revision.updated = now;
+                changed = true;
+            }
+            if now - revision.seen >= Duration::hours(SEEN_INTERVAL_HOURS) {
+                revision.seen = now;
+                changed = true;
+            }
+            revisions.push(Revision {
+                sequence: revision.sequence,
+                updated: revision.updated,
+            });
+        }
+        evict(&mut file.events, MAX_EVENTS);
+        if changed {
+            self.persist(&file).await?;
+        }
+        return Ok(revisions);
+    }
+
+    /// Write `file` to disk
+    async fn persist(&self, file: &EventSequenceFile) -> Result<()> {
+        let contents = toml::to_string(file)?;
+        // Write to a temporary file first, so a failed write doesn't corrupt existing sequences
+        let tmp_path = self.path.with_extension("toml.tmp");
+        tokio::fs::write(&tmp_path, contents).await?;
+        tokio::fs::rename(&tmp_path, &self.path).await?;
+        return Ok(());
+    }
+}
+
+/// Forget the revisions of the events seen the longest time ago, keeping at most `max_events`
+fn evict(events: &mut BTreeMap<String, EventRevision>, max_events: usize) {
+    if events.len() <= max_events {
+        return;
+    }
+    let mut oldest: Vec<(DateTime<Utc>, String)> = events
+        .iter()
+        .map(|(uid, revision)| (revision.seen, uid.clone()))
+        .collect();
+    oldest.sort();
+    for (_, uid) in oldest.into_iter().take(events.len() - max_events) {
+        events.remove(&uid);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    /// Sequence is only bumped when the time of an event changes
+    async fn can_bump_sequence() {
+        let path = std::env::temp_dir().join(format!(
+            "fresh_meet_event_sequences_{}.toml",
+            std::process::id()
+        ));
+        let store = EventSequenceStore::load(&path).unwrap();
+        let event = |start: &str| {
+            (
+                "1@freshmeet".to_string(),
+                start.to_string(),
+                "2024-12-01T20:00:00-05:00".to_string(),
+            )
+        };
+
+        let first = store
+            .revisions(&[event("2024-12-01T18:00:00-05:00")])
+            .await
+            .unwrap();
+        assert_eq!(first[0].sequence, 0);
+        let revisions = store
+            .revisions(&[event("2024-12-01T18:00:00-05:00")])
+            .await
+            .unwrap();
+        assert_eq!(revisions, first);
+
+        let store = EventSequenceStore::load(&path).unwrap();
+        let revisions = store
+            .revisions(&[event("2024-12-01T18:30:00-05:00")])
+            .await
+            .unwrap();
+        assert_eq!(revisions[0].sequence, 1);
+        assert!(revisions[0].updated >= first[0].updated);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    /// Events that haven't changed in a long time but are still seen are kept
+    fn can_evict_by_last_seen() {
+        let now = Utc::now();
+        let revision = |updated: DateTime<Utc>, seen: DateTime<Utc>| EventRevision {
+            start: "2024-12-01T18:00:00-05:00".to_string(),
+            end: "2024-12-01T20:00:00-05:00".to_string(),
+            sequence: 0,
+            updated,
+            seen,
+        };
+        let mut events = BTreeMap::from([
+            (
+                "stable".to_string(),
+                revision(now - Duration::days(90), now),
+            ),
+            (
+                "gone".to_string(),
+                revision(now - Duration::days(2), now - Duration::days(1)),
+            ),
+            ("new".to_string(), revision(now, now)),
+        ]);
+
+        evict(&mut events, 2);
+        assert_eq!(
+            events.keys().collect::<Vec<&String>>(),
+            vec!["new", "stable"]
+        );
+    }
+}
@@ -0,0 +1,217 @@
+//! Export events as CSV or JSON Lines
+
+use crate::meetup::query::request::gql2::Edge;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Formats events can be exported in.
+///
+/// Every event is flattened into the columns of `EventRow`, in this order: `id`, `title`,
+/// `start`, `venue_name`, `venue_city`, `group_name`, `rsvp_count`, `fee_currency`, `url`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// Comma separated values, with a header row
+    Csv,
+    /// One JSON object per line
+    Jsonl,
+}
+
+impl ExportFormat {
+    /// Content type of an export in this format
+    pub fn content_type(&self) -> &'static str {
+        return match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Jsonl => "application/jsonl; charset=utf-8",
+        };
+    }
+
+    /// File extension of an export in this format
+    pub fn extension(&self) -> &'static str {
+        return match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Jsonl => "jsonl",
+        };
+    }
+
+    /// Line written before any events
+    pub fn header(&self) -> Option<String> {
+        return match self {
+            ExportFormat::Csv => Some(csv_line(&EventRow::COLUMNS)),
+            ExportFormat::Jsonl => None,
+        };
+    }
+
+    /// Render `edges`, one event per line
+    pub fn render<'a>(&self, edges: impl IntoIterator<Item = &'a Edge>) -> String {
+        return edges
+            .into_iter()
+            .map(|edge| {
+                let row = EventRow::from(edge);
+                match self {
+                    ExportFormat::Csv => csv_line(&row.values()),
+                    ExportFormat::Jsonl => {
+                        let mut line = serde_json::to_string(&row)
+                            .expect("Event rows only contain strings and numbers");
+                        line.push('\n');
+                        line
+                    }
+                }
+            })
+            .collect();
+    }
+}
+
+/// An event flattened into a single row
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EventRow {
+    pub id: String,
+    pub title: String,
+    /// Start date time in RFC 3339
+    pub start: String,
+    pub venue_name: String,
+    pub venue_city: String,
+    pub group_name: String,
+    pub rsvp_count: i64,
+    pub fee_currency: String,
+    pub url: String,
+}
+
+impl EventRow {
+    /// Names of the columns, in the same order as the fields
+    pub const COLUMNS: [&'static str; 9] = [
+        "id",
+        "title",
+        "start",
+        "venue_name",
+        "venue_city",
+        "group_name",
+        "rsvp_count",
+        "fee_currency",
+        "url",
+    ];
+
+    /// Values of the columns, in the same order as `COLUMNS`
+    fn values(&self) -> [String; 9] {
+        return [
+            self.id.clone(),
+            self.title.clone(),
+            self.start.clone(),
+            self.venue_name.clone(),
+            self.venue_city.clone(),
+            self.group_name.clone(),
+            self.rsvp_count.to_string(),
+            self.fee_currency.clone(),
+            self.url.clone(),
+        ];
+    }
+}
+
+impl From<&Edge> for EventRow {
+    fn from(edge: &Edge) -> Self {
+        let node = &edge.node;
+        let venue = node.venue.clone().unwrap_or_default();
+        return Self {
+            id: node.id.clone(),
+            title: node.title.clone(),
+            start: node.date_time.clone(),
+            venue_name: venue.name,
+            venue_city: venue.city,
+            group_name: node.group.name.clone(),
+            rsvp_count: node.rsvps.total_count,
+            fee_currency: node
+                .fee_settings
+                .as_ref()
+                .map(|f| f.currency.clone())
+                .unwrap_or_default(),
+            url: node.event_url.clone(),
+        };
+    }
+}
+
+/// Join `values` into a CSV line, quoting values when needed. Values that spreadsheets would
+/// run as a formula are prefixed with `'` so they are shown as text
+fn csv_line<S: AsRef<str>>(values: &[S]) -> String {
+    let mut line = values
+        .iter()
+        .map(|value| {
+            let mut value = value.as_ref().to_string();
+            if value.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+                value.insert(0, '\'');
+            }
+            if value.contains([',', '"', '\n', '\r']) {
+                return format!("\"{}\"", value.replace('"', "\"\""));
+            }
+            return value;
+        })
+        .collect::<Vec<String>>()
+        .join(",");
+    line.push_str("\r\n");
+    return line;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::meetup::query::request::gql2::{FeeSettings, Group, Node, Rsvps};
+
+    fn edge() -> Edge {
+        return Edge {
+            node: Node {
+                id: "1".into(),
+                title: "Rust, \"async\" and you".into(),
+                date_time: "2024-12-01T18:00:00-05:00".into(),
+                event_url: "https://www.meetup.com/rust-toronto/events/1/".into(),
+                group: Group {
+                    name: "Rust Toronto".into(),
+                    ..Default::default()
+                },
+                rsvps: Rsvps { total_count: 42 },
+                fee_settings: Some(FeeSettings {
+                    accepts: "CARD".into(),
+                    currency: "CAD".into(),
+                }),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+    }
+
+    #[test]
+    /// Events are exported as CSV in the documented column order
+    fn can_export_csv() {
+        assert_eq!(
+            ExportFormat::Csv.header().unwrap(),
+            "id,title,start,venue_name,venue_city,group_name,rsvp_count,fee_currency,url\r\n"
+        );
+        assert_eq!(
+            ExportFormat::Csv.render([&edge()]),
+            "1,\"Rust, \"\"async\"\" and you\",2024-12-01T18:00:00-05:00,,,Rust Toronto,42,CAD,https://www.meetup.com/rust-toronto/events/1/\r\n"
+        );
+    }
+
+    #[test]
+    /// Titles and group names that spreadsheets would run as a formula are exported as text
+    fn can_neutralize_formulas() {
+        let mut edge = edge();
+        edge.node.title = "=HYPERLINK(\"https://example.com\")".into();
+        edge.node.group.name = "@Rust".into();
+        let line = ExportFormat::Csv.render([&edge]);
+        assert!(line.starts_with(
+            "1,\"'=HYPERLINK(\"\"https://example.com\"\")\",2024-12-01T18:00:00-05:00,,,'@Rust,42,"
+        ));
+        assert_eq!(csv_line(&["-1", "+1", "a-b"]), "'-1,'+1,a-b\r\n");
+    }
+
+    #[test]
+    /// Events are exported as one JSON object per line, with the same columns as CSV
+    fn can_export_jsonl() {
+        let line = ExportFormat::Jsonl.render([&edge()]);
+        assert!(line.ends_with('\n'));
+        let row: serde_json::Map<String, serde_json::Value> =
+            serde_json::from_str(line.trim_end()).unwrap();
+        assert_eq!(row.keys().cloned().collect::<Vec<String>>().len(), 9);
+        assert!(EventRow::COLUMNS.iter().all(|c| row.contains_key(*c)));
+        assert_eq!(row["rsvp_count"], 42);
+    }
+}
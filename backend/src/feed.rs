@@ -0,0 +1,248 @@
+//! Render events as Atom and RSS 2.0 feeds
+
+use axum::http::{header, HeaderMap};
+use chrono::{DateTime, Utc};
+
+/// Formats a feed can be rendered in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FeedFormat {
+    Atom,
+    Rss,
+}
+
+impl FeedFormat {
+    /// Get the feed format requested by the `Accept` header, if any. A feed is only returned
+    /// when no other media type is preferred over it by its `q` value
+    pub fn from_accept(headers: &HeaderMap) -> Option<Self> {
+        let accept = headers.get(header::ACCEPT)?.to_str().ok()?;
+        let media_ranges: Vec<(Option<Self>, f32)> = accept
+            .split(',')
+            .map(|media_range| {
+                let mut params = media_range.split(';');
+                let format = match params.next().unwrap_or_default().trim() {
+                    "application/atom+xml" => Some(FeedFormat::Atom),
+                    "application/rss+xml" => Some(FeedFormat::Rss),
+                    _ => None,
+                };
+                let quality = params
+                    .find_map(|param| param.trim().strip_prefix("q="))
+                    .and_then(|q| q.trim().parse().ok())
+                    .unwrap_or(1.0);
+                return (format, quality);
+            })
+            .collect();
+        let highest = media_ranges.iter().map(|(_, q)| *q).fold(0.0, f32::max);
+        if highest <= 0.0 {
+            return None;
+        }
+        return media_ranges
+            .into_iter()
+            .find_map(|(format, quality)| format.filter(|_| quality >= highest));
+    }
+
+    /// Content type of a feed in this format
+    pub fn content_type(&self) -> &'static str {
+        return match self {
+            FeedFormat::Atom => "application/atom+xml; charset=utf-8",
+            FeedFormat::Rss => "application/rss+xml; charset=utf-8",
+        };
+    }
+}
+
+/// A feed of events
+#[derive(Debug, Clone, PartialEq)]
+pub struct Feed {
+    /// Permanent, unique ID of this feed
+    pub id: String,
+    pub title: String,
+    /// Web page this feed is for
+    pub link: String,
+    pub entries: Vec<FeedEntry>,
+}
+
+/// A single event in a feed
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeedEntry {
+    /// Permanent, unique ID of this entry
+    pub id: String,
+    pub title: String,
+    /// Event URL
+    pub link: String,
+    /// Name of the group hosting the event
+    pub author: String,
+    /// HTML content
+    pub content: String,
+    /// When the event last changed
+    pub updated: DateTime<Utc>,
+}
+
+impl Feed {
+    /// When any entry in this feed last changed
+    fn updated(&self) -> DateTime<Utc> {
+        return self
+            .entries
+            .iter()
+            .map(|e| e.updated)
+            .max()
+            .unwrap_or(DateTime::UNIX_EPOCH);
+    }
+
+    /// Render this feed in `format`
+    pub fn render(&self, format: FeedFormat) -> String {
+        return match format {
+            FeedFormat::Atom => self.to_atom(),
+            FeedFormat::Rss => self.to_rss(),
+        };
+    }
+
+    fn to_atom(&self) -> String {
+        let mut xml = String::from(r#"<?xml version="1.0" encoding="utf-8"?>"#);
+        xml.push_str(r#"<feed xmlns="http://www.w3.org/2005/Atom">"#);
+        xml.push_str(&format!("<id>{}</id>", escape(&self.id)));
+        xml.push_str(&format!("<title>{}</title>", escape(&self.title)));
+        xml.push_str(&format!(r#"<link href="{}"/>"#, escape(&self.link)));
+        xml.push_str(&format!(
+            "<updated>{}</updated>",
+            self.updated().to_rfc3339()
+        ));
+        for entry in &self.entries {
+            xml.push_str("<entry>");
+            xml.push_str(&format!("<id>{}</id>", escape(&entry.id)));
+            xml.push_str(&format!("<title>{}</title>", escape(&entry.title)));
+            xml.push_str(&format!(r#"<link href="{}"/>"#, escape(&entry.link)));
+            xml.push_str(&format!(
+                "<author><name>{}</name></author>",
+                escape(&entry.author)
+            ));
+            xml.push_str(&format!(
+                "<updated>{}</updated>",
+                entry.updated.to_rfc3339()
+            ));
+            xml.push_str(&format!(
+                r#"<content type="html">{}</content>"#,
+                escape(&entry.content)
+            ));
+            xml.push_str("</entry>");
+        }
+        xml.push_str("</feed>");
+        return xml;
+    }
+
+    fn to_rss(&self) -> String {
+        let mut xml = String::from(r#"<?xml version="1.0" encoding="utf-8"?>"#);
+        xml.push_str(r#"<rss version="2.0" xmlns:dc="http://purl.org/dc/elements/1.1/"><channel>"#);
+        xml.push_str(&format!("<title>{}</title>", escape(&self.title)));
+        xml.push_str(&format!("<link>{}</link>", escape(&self.link)));
+        xml.push_str(&format!(
+            "<description>{}</description>",
+            escape(&self.title)
+        ));
+        xml.push_str(&format!(
+            "<lastBuildDate>{}</lastBuildDate>",
+            self.updated().to_rfc2822()
+        ));
+        for entry in &self.entries {
+            xml.push_str("<item>");
+            xml.push_str(&format!("<title>{}</title>", escape(&entry.title)));
+            xml.push_str(&format!("<link>{}</link>", escape(&entry.link)));
+            xml.push_str(&format!(
+                r#"<guid isPermaLink="false">{}</guid>"#,
+                escape(&entry.id)
+            ));
+            // RSS expects an email address in `author`, use Dublin Core for a plain name instead
+            xml.push_str(&format!(
+                "<dc:creator>{}</dc:creator>",
+                escape(&entry.author)
+            ));
+            xml.push_str(&format!(
+                "<pubDate>{}</pubDate>",
+                entry.updated.to_rfc2822()
+            ));
+            xml.push_str(&format!(
+                "<description>{}</description>",
+                escape(&entry.content)
+            ));
+            xml.push_str("</item>");
+        }
+        xml.push_str("</channel></rss>");
+        return xml;
+    }
+}
+
+/// Escape special characters in XML or HTML text and attribute values
+pub fn escape(text: &str) -> String {
+    return text
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;");
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn feed() -> Feed {
+        return Feed {
+            id: "urn:freshmeet:recommended".into(),
+            title: "FreshMeet recommended".into(),
+            link: "https://www.meetup.com/find/".into(),
+            entries: vec![FeedEntry {
+                id: "urn:freshmeet:event:1".into(),
+                title: "Rust & friends".into(),
+                link: "https://www.meetup.com/rust-toronto/events/1/".into(),
+                author: "Rust Toronto".into(),
+                content: "<p>Talks</p>".into(),
+                updated: DateTime::parse_from_rfc3339("2024-11-20T12:00:00Z")
+                    .unwrap()
+                    .to_utc(),
+            }],
+        };
+    }
+
+    #[test]
+    /// Feeds are rendered as Atom with escaped content
+    fn can_render_atom() {
+        let xml = feed().render(FeedFormat::Atom);
+        assert!(xml.contains("<title>Rust &amp; friends</title>"));
+        assert!(xml.contains(r#"<content type="html">&lt;p&gt;Talks&lt;/p&gt;</content>"#));
+        assert!(xml.contains("<author><name>Rust Toronto</name></author>"));
+        assert!(xml.contains("<updated>2024-11-20T12:00:00+00:00</updated>"));
+    }
+
+    #[test]
+    /// Feeds are rendered as RSS with RFC 2822 dates
+    fn can_render_rss() {
+        let xml = feed().render(FeedFormat::Rss);
+        assert!(xml.contains(r#"<guid isPermaLink="false">urn:freshmeet:event:1</guid>"#));
+        assert!(xml.contains("<pubDate>Wed, 20 Nov 2024 12:00:00 +0000</pubDate>"));
+        assert!(xml.contains("<dc:creator>Rust Toronto</dc:creator>"));
+    }
+
+    #[test]
+    /// Feed format is negotiated with the `Accept` header
+    fn can_negotiate_format() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(FeedFormat::from_accept(&headers), None);
+        headers.insert(
+            header::ACCEPT,
+            HeaderValue::from_static("text/html;q=0.8, application/rss+xml;q=0.9"),
+        );
+        assert_eq!(FeedFormat::from_accept(&headers), Some(FeedFormat::Rss));
+        for accept in [
+            "text/html, application/rss+xml;q=0.9",
+            "application/json, application/atom+xml;q=0.1",
+            "application/atom+xml;q=0",
+        ] {
+            headers.insert(header::ACCEPT, HeaderValue::from_static(accept));
+            assert_eq!(FeedFormat::from_accept(&headers), None, "Accept: {accept}");
+        }
+        headers.insert(
+            header::ACCEPT,
+            HeaderValue::from_static("application/atom+xml, */*;q=0.1"),
+        );
+        assert_eq!(FeedFormat::from_accept(&headers), Some(FeedFormat::Atom));
+    }
+}
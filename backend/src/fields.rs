@@ -0,0 +1,284 @@
+//! Choosing which fields of events are returned
+
+use crate::meetup::query::request::gql2::{DescriptionField, GQLResponse, Node};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap};
+use utoipa::openapi::schema::{ArrayItems, Schema, SchemaType};
+use utoipa::openapi::RefOr;
+use utoipa::{IntoParams, PartialSchema, ToSchema};
+
+/// Query parameters for choosing the fields of events
+#[derive(Debug, Clone, Default, Deserialize, IntoParams)]
+pub struct FieldsQueryParams {
+    /// Comma separated fields to return for every event, as dotted paths or JSON pointers into
+    /// the `node` of an event, ie `title,dateTime,venue.name,/group/name`. Fields of events in
+    /// lists, ie `crossPostedGroups.name`, are taken from every event in the list. All fields are
+    /// returned if not set.
+    ///
+    /// `descriptionText`, `descriptionSummary` and `descriptionMarkdown` are only returned when
+    /// requested
+    ///
+    /// When set, the `node` of every event is partial: it only has the requested fields, even
+    /// where the response schema lists a field as required. The rest of the response, including
+    /// the `metadata` of every event, is returned as is.
+    pub fields: Option<String>,
+}
+
+/// Requested fields of an object
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldTree {
+    /// The whole value is returned
+    All,
+    /// Only these fields are returned
+    Fields(BTreeMap<String, FieldTree>),
+}
+
+impl FieldTree {
+    /// Add the field at `path` to the requested fields
+    fn insert(&mut self, path: &[String]) {
+        let FieldTree::Fields(fields) = self else {
+            // Already returning everything
+            return;
+        };
+        let Some((first, rest)) = path.split_first() else {
+            *self = FieldTree::All;
+            return;
+        };
+        fields
+            .entry(first.clone())
+            .or_insert_with(|| FieldTree::Fields(BTreeMap::new()))
+            .insert(rest);
+    }
+
+    /// Whether any part of the field at `path` is requested
+    pub fn contains(&self, path: &[&str]) -> bool {
+        let FieldTree::Fields(fields) = self else {
+            return true;
+        };
+        let Some((first, rest)) = path.split_first() else {
+            return true;
+        };
+        return fields.get(*first).is_some_and(|tree| tree.contains(rest));
+    }
+
+    /// Copy of `value` with only the requested fields. Lists are projected element by element
+    pub fn project(&self, value: &Value) -> Value {
+        let FieldTree::Fields(fields) = self else {
+            return value.clone();
+        };
+        return match value {
+            Value::Array(items) => Value::Array(items.iter().map(|v| self.project(v)).collect()),
+            Value::Object(object) => Value::Object(
+                fields
+                    .iter()
+                    .filter_map(|(name, tree)| {
+                        Some((name.clone(), tree.project(object.get(name)?)))
+                    })
+                    .collect::<Map<String, Value>>(),
+            ),
+            _ => value.clone(),
+        };
+    }
+}
+
+impl FieldsQueryParams {
+    /// Paths to the requested fields
+    fn paths(&self) -> Result<Vec<Vec<String>>, String> {
+        let Some(fields) = &self.fields else {
+            return Ok(vec![]);
+        };
+        return fields
+            .split(',')
+            .map(str::trim)
+            .filter(|field| !field.is_empty())
+            .map(parse_path)
+            .collect();
+    }
+
+    /// Requested fields of events, checked against the schema of `Node`. `None` if all fields are
+    /// requested
+    pub fn field_tree(&self) -> Result<Option<FieldTree>, String> {
+        let paths = self.paths()?;
+        if paths.is_empty() {
+            return Ok(None);
+        }
+        let mut schemas = vec![];
+        <Node as ToSchema>::schemas(&mut schemas);
+        let schemas: HashMap<String, RefOr<Schema>> = schemas.into_iter().collect();
+        let node = Node::schema();
+
+        let mut tree = FieldTree::Fields(BTreeMap::new());
+        for path in paths {
+            if !has_path(&node, &path, &schemas) {
+                return Err(format!("Unknown field `{}`", path.join(".")));
+            }
+            tree.insert(&path);
+        }
+        return Ok(Some(tree));
+    }
+
+    /// Extra description fields to compute
+    pub fn description_fields(&self) -> Result<Vec<DescriptionField>, String> {
+        let mut description_fields = vec![];
+        for path in self.paths()? {
+            if let Ok(field) = path[0].parse::<DescriptionField>() {
+                if !description_fields.contains(&field) {
+                    description_fields.push(field);
+                }
+            }
+        }
+        return Ok(description_fields);
+    }
+}
+
+/// Project the `node` of every event in `res` to the fields in `tree`. Other fields of events are
+/// kept
+pub fn project(res: &GQLResponse, tree: &FieldTree) -> Value {
+    let mut value = serde_json::to_value(res).expect("Responses are always valid JSON");
+    if let Some(Value::Array(edges)) = value.pointer_mut("/data/result/edges") {
+        for edge in edges {
+            if let Some(node) = edge.get_mut("node") {
+                *node = tree.project(node);
+            }
+        }
+    }
+    return value;
+}
+
+/// Split a dotted path or JSON pointer into its segments
+fn parse_path(field: &str) -> Result<Vec<String>, String> {
+    let segments: Vec<String> = match field.strip_prefix('/') {
+        Some(pointer) => pointer
+            .split('/')
+            .map(|s| s.replace("~1", "/").replace("~0", "~"))
+            .collect(),
+        None => field.split('.').map(String::from).collect(),
+    };
+    if segments.iter().any(String::is_empty) {
+        return Err(format!("Invalid field `{field}`"));
+    }
+    return Ok(segments);
+}
+
+/// Whether `path` leads to a field in `schema`. `schemas` are the schemas that can be referenced
+fn has_path(
+    schema: &RefOr<Schema>,
+    path: &[String],
+    schemas: &HashMap<String, RefOr<Schema>>,
+) -> bool {
+    let Some((first, rest)) = path.split_first() else {
+        return true;
+    };
+    let schema = match schema {
+        RefOr::Ref(reference) => {
+            let name = reference
+                .ref_location
+                .rsplit('/')
+                .next()
+                .unwrap_or_default();
+            match schemas.get(name) {
+                Some(schema) => return has_path(schema, path, schemas),
+                None => return false,
+            }
+        }
+        RefOr::T(schema) => schema,
+    };
+    return match schema {
+        Schema::Object(object) => match object.properties.get(first) {
+            Some(property) => has_path(property, rest, schemas),
+            // Maps accept any key, and untyped values can have any field
+            None => {
+                object.additional_properties.is_some() || object.schema_type == SchemaType::AnyValue
+            }
+        },
+        Schema::Array(array) => match &array.items {
+            ArrayItems::RefOrSchema(items) => has_path(items, path, schemas),
+            ArrayItems::False => false,
+        },
+        Schema::OneOf(one_of) => one_of.items.iter().any(|s| has_path(s, path, schemas)),
+        Schema::AllOf(all_of) => all_of.items.iter().any(|s| has_path(s, path, schemas)),
+        Schema::AnyOf(any_of) => any_of.items.iter().any(|s| has_path(s, path, schemas)),
+        _ => false,
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::meetup::query::request::gql2::{Group, Venue};
+
+    fn fields(fields: &str) -> FieldsQueryParams {
+        return FieldsQueryParams {
+            fields: Some(fields.into()),
+        };
+    }
+
+    #[test]
+    /// Fields are checked against the schema of an event
+    fn can_validate_fields() {
+        assert!(
+            fields("title,venue.name,/group/name,crossPostedGroups.name")
+                .field_tree()
+                .is_ok()
+        );
+        assert!(fields("travelMinutes.walking,socialLabels")
+            .field_tree()
+            .is_ok());
+        assert_eq!(
+            fields("title,venue.nme").field_tree(),
+            Err("Unknown field `venue.nme`".to_string())
+        );
+        assert_eq!(
+            fields("title..name").field_tree(),
+            Err("Invalid field `title..name`".to_string())
+        );
+        assert_eq!(FieldsQueryParams::default().field_tree(), Ok(None));
+    }
+
+    #[test]
+    /// Events only contain the requested fields
+    fn can_project_fields() {
+        let mut res = GQLResponse::from_nodes(vec![Node {
+            id: "1".into(),
+            title: "Rust Toronto".into(),
+            venue: Some(Venue {
+                name: "Union Station".into(),
+                city: "Toronto".into(),
+                ..Default::default()
+            }),
+            group: Group {
+                name: "Rust".into(),
+                ..Default::default()
+            },
+            ..Default::default()
+        }]);
+        res.data.as_mut().unwrap().result.edges[0].metadata.rec_id = "rec-1".into();
+        let tree = fields("title,venue.city,/group/name,venue.name,descriptionText")
+            .field_tree()
+            .unwrap()
+            .unwrap();
+        assert!(tree.contains(&["venue"]));
+        assert!(tree.contains(&["venue", "city"]));
+        assert!(!tree.contains(&["venue", "address"]));
+        assert!(!tree.contains(&["mapUrl"]));
+        let value = project(&res, &tree);
+        assert_eq!(value["data"]["result"]["totalCount"], 1);
+        assert_eq!(
+            value["data"]["result"]["edges"][0],
+            serde_json::json!({
+                "node": {
+                    "title": "Rust Toronto",
+                    "venue": { "name": "Union Station", "city": "Toronto" },
+                    "group": { "name": "Rust" },
+                },
+                "metadata": { "recId": "rec-1", "recSource": "" },
+            })
+        );
+        assert_eq!(
+            fields("title,descriptionText,descriptionSummary").description_fields(),
+            Ok(vec![DescriptionField::Text, DescriptionField::Summary])
+        );
+        assert!(fields("description_text").field_tree().is_err());
+    }
+}
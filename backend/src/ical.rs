@@ -0,0 +1,337 @@
+//! Render events as iCalendar (RFC 5545) calendars
+
+use crate::meetup::model::Event;
+use crate::utils::markdown_to_text;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration, NaiveDateTime, Offset, TimeZone, Utc};
+use chrono_tz::{OffsetComponents, OffsetName, Tz};
+use std::collections::BTreeMap;
+
+/// Identifies FreshMeet as the product that created a calendar
+const PRODUCT_ID: &str = "-//FreshMeet//FreshMeet Calendar//EN";
+
+/// Meetup doesn't tell us when an event ends, so events are assumed to be this long
+const DEFAULT_DURATION_HOURS: i64 = 2;
+
+/// Maximum length of a line in octets, excluding the line break
+const MAX_LINE_LENGTH: usize = 75;
+
+/// A single event in a calendar
+#[derive(Debug, Clone, PartialEq)]
+pub struct CalendarEvent {
+    /// Globally unique ID of this event
+    pub uid: String,
+    /// Revision of this event. Should be increased every time the event changes
+    pub sequence: u32,
+    pub start: DateTime<Tz>,
+    pub end: DateTime<Tz>,
+    pub summary: String,
+    /// Plain text description
+    pub description: String,
+    pub url: String,
+    pub location: Option<String>,
+    /// Latitude and longitude of the venue
+    pub geo: Option<(f64, f64)>,
+}
+
+impl TryFrom<&Event> for CalendarEvent {
+    type Error = anyhow::Error;
+
+    fn try_from(event: &Event) -> Result<Self> {
+        // Fall back to UTC if Meetup gives us a time zone we don't know about
+        let tz: Tz = event.group.timezone.parse().unwrap_or(Tz::UTC);
+        let start = DateTime::parse_from_rfc3339(&event.date_time)
+            .map_err(|e| anyhow!("Invalid start date `{}`: {}", event.date_time, e))?
+            .with_timezone(&tz);
+        let end = match &event.end_time {
+            Some(end_time) => DateTime::parse_from_rfc3339(end_time)
+                .map_err(|e| anyhow!("Invalid end date `{}`: {}", end_time, e))?
+                .with_timezone(&tz),
+            None => start + Duration::hours(DEFAULT_DURATION_HOURS),
+        };
+        let description = match &event.description {
+            Some(description) => markdown_to_text(description),
+            None => format!("Hosted by {}", event.group.name),
+        };
+
+        return Ok(Self {
+            uid: uid(&event.id),
+            sequence: 0,
+            start,
+            end,
+            summary: event.title.clone(),
+            description,
+            url: event.event_url.clone(),
+            location: event
+                .venue
+                .as_ref()
+                .map(|v| v.location())
+                .filter(|location| !location.is_empty()),
+            geo: event.venue.as_ref().and_then(|v| v.coordinates),
+        });
+    }
+}
+
+/// Stable calendar UID of the Meetup event `id`
+pub fn uid(id: &str) -> String {
+    return format!("{id}@freshmeet");
+}
+
+/// Render `events` as a calendar called `name`
+pub fn render(name: &str, events: &[CalendarEvent]) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        format!("PRODID:{PRODUCT_ID}"),
+        "CALSCALE:GREGORIAN".to_string(),
+        "METHOD:PUBLISH".to_string(),
+        format!("X-WR-CALNAME:{}", escape(name)),
+    ];
+
+    // Each time zone only needs to cover the events that use it
+    let mut time_zones: BTreeMap<&str, (Tz, DateTime<Utc>, DateTime<Utc>)> = BTreeMap::new();
+    for event in events {
+        let tz = event.start.timezone();
+        let (start, end) = (event.start.to_utc(), event.end.to_utc());
+        time_zones
+            .entry(tz.name())
+            .and_modify(|(_, from, to)| {
+                *from = (*from).min(start);
+                *to = (*to).max(end);
+            })
+            .or_insert((tz, start, end));
+    }
+    for (tz, from, to) in time_zones.values() {
+        if *tz != Tz::UTC {
+            lines.extend(vtimezone(*tz, *from, *to));
+        }
+    }
+
+    let now = format_utc(Utc::now());
+    for event in events {
+        lines.push("BEGIN:VEVENT".to_string());
+        lines.push(format!("UID:{}", event.uid));
+        lines.push(format!("DTSTAMP:{now}"));
+        lines.push(format!("SEQUENCE:{}", event.sequence));
+        lines.push(format_date_time("DTSTART", &event.start));
+        lines.push(format_date_time("DTEND", &event.end));
+        lines.push(format!("SUMMARY:{}", escape(&event.summary)));
+        if !event.description.is_empty() {
+            lines.push(format!("DESCRIPTION:{}", escape(&event.description)));
+        }
+        lines.push(format!("URL:{}", event.url));
+        if let Some(location) = &event.location {
+            lines.push(format!("LOCATION:{}", escape(location)));
+        }
+        if let Some((lat, lon)) = event.geo {
+            lines.push(format!("GEO:{lat:.6};{lon:.6}"));
+        }
+        lines.push("END:VEVENT".to_string());
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    return lines.iter().map(|line| fold(line)).collect();
+}
+
+/// A `VTIMEZONE` component for `tz`, describing the UTC offsets in effect between `from` and `to`
+fn vtimezone(tz: Tz, from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<String> {
+    let mut lines = vec!["BEGIN:VTIMEZONE".to_string(), format!("TZID:{}", tz.name())];
+
+    // The offset in effect at the first event
+    let offset = tz.offset_from_utc_datetime(&from.naive_utc());
+    let seconds = offset.fix().local_minus_utc();
+    lines.extend(observance(
+        offset.dst_offset() != Duration::zero(),
+        from.with_timezone(&tz).naive_local(),
+        seconds,
+        seconds,
+        offset.abbreviation(),
+    ));
+
+    // Every change in offset until the last event
+    for at in transitions(tz, from, to) {
+        let before = tz.offset_from_utc_datetime(&(at - Duration::seconds(1)).naive_utc());
+        let after = tz.offset_from_utc_datetime(&at.naive_utc());
+        let offset_from = before.fix().local_minus_utc();
+        lines.extend(observance(
+            after.dst_offset() != Duration::zero(),
+            // Start of an observance is given in the offset before the change
+            at.naive_utc() + Duration::seconds(offset_from as i64),
+            offset_from,
+            after.fix().local_minus_utc(),
+            after.abbreviation(),
+        ));
+    }
+
+    lines.push("END:VTIMEZONE".to_string());
+    return lines;
+}
+
+/// A `STANDARD` or `DAYLIGHT` observance starting at `start`
+fn observance(
+    is_dst: bool,
+    start: NaiveDateTime,
+    offset_from: i32,
+    offset_to: i32,
+    name: Option<&str>,
+) -> Vec<String> {
+    let kind = if is_dst { "DAYLIGHT" } else { "STANDARD" };
+    let mut lines = vec![
+        format!("BEGIN:{kind}"),
+        format!("DTSTART:{}", start.format("%Y%m%dT%H%M%S")),
+        format!("TZOFFSETFROM:{}", format_offset(offset_from)),
+        format!("TZOFFSETTO:{}", format_offset(offset_to)),
+    ];
+    if let Some(name) = name {
+        lines.push(format!("TZNAME:{}", escape(name)));
+    }
+    lines.push(format!("END:{kind}"));
+    return lines;
+}
+
+/// Instants between `from` and `to` where the UTC offset of `tz` changes
+fn transitions(tz: Tz, from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<DateTime<Utc>> {
+    let offset_at = |at: DateTime<Utc>| tz.offset_from_utc_datetime(&at.naive_utc()).fix();
+
+    let mut transitions = vec![];
+    let mut day = from;
+    while day < to {
+        let next_day = (day + Duration::days(1)).min(to);
+        if offset_at(day) != offset_at(next_day) {
+            // Offsets change at most once a day, find the exact second it changes
+            let (mut before, mut after) = (day, next_day);
+            while after - before > Duration::seconds(1) {
+                let middle = before + (after - before) / 2;
+                if offset_at(middle) == offset_at(day) {
+                    before = middle;
+                } else {
+                    after = middle;
+                }
+            }
+            transitions.push(after);
+        }
+        day = next_day;
+    }
+    return transitions;
+}
+
+/// Format a date time property, using UTC or a reference to a `VTIMEZONE`
+fn format_date_time(property: &str, date_time: &DateTime<Tz>) -> String {
+    if date_time.timezone() == Tz::UTC {
+        return format!("{property}:{}", format_utc(date_time.to_utc()));
+    }
+    return format!(
+        "{property};TZID={tz}:{local}",
+        tz = date_time.timezone().name(),
+        local = date_time.format("%Y%m%dT%H%M%S")
+    );
+}
+
+fn format_utc(date_time: DateTime<Utc>) -> String {
+    return date_time.format("%Y%m%dT%H%M%SZ").to_string();
+}
+
+/// Format a UTC offset in seconds as `+HHMM`, or `+HHMMSS` if it is not a whole minute
+fn format_offset(seconds: i32) -> String {
+    let sign = if seconds < 0 { '-' } else { '+' };
+    let seconds = seconds.abs();
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if seconds == 0 {
+        return format!("{sign}{hours:02}{minutes:02}");
+    }
+    return format!("{sign}{hours:02}{minutes:02}{seconds:02}");
+}
+
+/// Escape special characters in a text value
+fn escape(text: &str) -> String {
+    return text
+        .replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace(['\n', '\r'], "\\n");
+}
+
+/// Split `line` into lines of at most 75 octets, and terminate it with CRLF
+fn fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len() + line.len() / MAX_LINE_LENGTH * 3 + 2);
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > MAX_LINE_LENGTH {
+            folded.push_str("\r\n ");
+            // The leading space counts towards the line length
+            length = 1;
+        }
+        folded.push(c);
+        length += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+    return folded;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::meetup::query::request::gql2::{Edge, Group, Node, Venue};
+
+    #[test]
+    /// Events are rendered with their time zone, and long lines are folded
+    fn can_render_calendar() {
+        let edge = Edge {
+            node: Node {
+                id: "304571953".into(),
+                title: "Rust Toronto; December meetup".into(),
+                date_time: "2024-12-01T18:00:00-05:00".into(),
+                description: "**Talks** about `async`, and more. ".repeat(5),
+                event_url: "https://www.meetup.com/rust-toronto/events/304571953/".into(),
+                group: Group {
+                    timezone: "America/Toronto".into(),
+                    ..Default::default()
+                },
+                venue: Some(Venue {
+                    name: "Shopify".into(),
+                    city: "Toronto".into(),
+                    lat: 43.6426,
+                    lon: -79.3871,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let event = CalendarEvent::try_from(&Event::from(&edge.node)).unwrap();
+        let calendar = render("Recommended", &[event]);
+
+        assert!(calendar.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(calendar.ends_with("END:VCALENDAR\r\n"));
+        assert!(calendar.contains("TZID:America/Toronto\r\n"));
+        assert!(calendar.contains("TZOFFSETTO:-0500\r\n"));
+        assert!(calendar.contains("UID:304571953@freshmeet\r\n"));
+        assert!(calendar.contains("DTSTART;TZID=America/Toronto:20241201T180000\r\n"));
+        assert!(calendar.contains("DTEND;TZID=America/Toronto:20241201T200000\r\n"));
+        assert!(calendar.contains("SUMMARY:Rust Toronto\\; December meetup\r\n"));
+        assert!(calendar.contains("DESCRIPTION:Talks about async\\, and more."));
+        assert!(calendar.contains("LOCATION:Shopify\\, Toronto\r\n"));
+        assert!(calendar.contains("GEO:43.642600;-79.387100\r\n"));
+        assert!(calendar
+            .split("\r\n")
+            .all(|line| line.len() <= MAX_LINE_LENGTH));
+    }
+
+    #[test]
+    /// Daylight saving time changes between events are included in the time zone
+    fn can_find_time_zone_transitions() {
+        let from = Utc.with_ymd_and_hms(2024, 10, 1, 0, 0, 0).unwrap();
+        let to = Utc.with_ymd_and_hms(2024, 12, 1, 0, 0, 0).unwrap();
+        let transitions = transitions(chrono_tz::America::Toronto, from, to);
+        assert_eq!(
+            transitions,
+            vec![Utc.with_ymd_and_hms(2024, 11, 3, 6, 0, 0).unwrap()]
+        );
+
+        let lines = vtimezone(chrono_tz::America::Toronto, from, to);
+        assert!(lines.contains(&"BEGIN:DAYLIGHT".to_string()));
+        assert!(lines.contains(&"DTSTART:20241103T020000".to_string()));
+        assert!(lines.contains(&"TZNAME:EST".to_string()));
+    }
+}
@@ -1,11 +1,32 @@
 #![allow(clippy::needless_return)]
+mod date_parser;
+mod date_range;
+mod dto;
+mod event_sequence;
+mod export;
+mod feed;
+mod fields;
+mod ical;
+mod map_link;
 mod meetup;
+mod notifier;
 mod routes;
+mod routing;
+mod sanitize;
+mod saved_search;
+mod time_filter;
 mod utils;
 
-use crate::routes::app;
-use anyhow::Result;
+use crate::event_sequence::EventSequenceStore;
+use crate::map_link::MapProvider;
+use crate::routes::{app, AppState};
+use crate::routing::TravelTimeRouter;
+use crate::sanitize::HtmlSanitizer;
+use crate::saved_search::SavedSearchStore;
+use anyhow::{anyhow, Result};
 use common_axum::axum::axum_serve;
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::net::TcpListener;
 use tracing::{info, Level};
 
@@ -17,8 +38,50 @@ async fn main() -> Result<()> {
         .compact()
         .init();
 
+    let saved_searches_path =
+        std::env::var("SAVED_SEARCHES_PATH").unwrap_or("saved_searches.toml".to_string());
+    let event_sequences_path =
+        std::env::var("EVENT_SEQUENCES_PATH").unwrap_or("event_sequences.toml".to_string());
+    let feed_revisions_path =
+        std::env::var("FEED_REVISIONS_PATH").unwrap_or("feed_revisions.toml".to_string());
+    let map_provider = match std::env::var("MAP_PROVIDER") {
+        Ok(provider) => provider.parse().map_err(|e: String| anyhow!(e))?,
+        Err(_) => MapProvider::default(),
+    };
+    let travel_time_router = match std::env::var("ROUTER_URL") {
+        Ok(url) => {
+            let kind = std::env::var("ROUTER_KIND")
+                .unwrap_or("osrm".to_string())
+                .parse()
+                .map_err(|e: String| anyhow!(e))?;
+            info!("Estimating travel times with {:?} at {}", kind, url);
+            Some(Arc::new(TravelTimeRouter::new(url, kind)))
+        }
+        Err(_) => None,
+    };
+    let state = AppState {
+        saved_searches: Arc::new(SavedSearchStore::load(saved_searches_path)?),
+        saved_search_secret: std::env::var("SAVED_SEARCH_SECRET").ok(),
+        event_sequences: Arc::new(EventSequenceStore::load(event_sequences_path)?),
+        feed_revisions: Arc::new(EventSequenceStore::load(feed_revisions_path)?),
+        rsvp_calendar_token: std::env::var("RSVP_CALENDAR_TOKEN").ok(),
+        map_provider,
+        travel_time_router,
+        html_sanitizer: HtmlSanitizer::new(std::env::var("IMAGE_PROXY_URL").ok()),
+        categories: Default::default(),
+    };
+
+    let notify_interval = std::env::var("NOTIFY_INTERVAL_SECS")
+        .ok()
+        .and_then(|secs| secs.parse().ok())
+        .unwrap_or(15 * 60);
+    tokio::spawn(notifier::run(
+        state.saved_searches.clone(),
+        Duration::from_secs(notify_interval),
+    ));
+
     let addr = "0.0.0.0:8000";
     let listener = TcpListener::bind(addr).await.unwrap();
     info!("Listening on {}", addr);
-    return axum_serve(listener, app()).await;
+    return axum_serve(listener, app(state)).await;
 }
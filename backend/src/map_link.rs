@@ -0,0 +1,255 @@
+//! Links to directions to an event venue in different map apps
+
+use crate::meetup::query::request::gql2::{GQLResponse, Venue};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use urlencoding::encode;
+use utoipa::{IntoParams, ToSchema};
+
+/// Builds a link to directions to a venue
+pub trait MapLinkProvider: Send + Sync {
+    /// Link to directions to `venue`, travelling by `mode`. Providers without support for a mode
+    /// fall back to their default mode
+    fn directions_url(&self, venue: &Venue, mode: Option<TravelMode>) -> String;
+}
+
+/// Map apps links can be generated for
+#[derive(Default, Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum MapProvider {
+    #[default]
+    Google,
+    Apple,
+    /// OpenStreetMap
+    Osm,
+    /// A `geo:` URI, opened by the default map app on mobile devices
+    Geo,
+}
+
+impl MapProvider {
+    /// Whether links of this map app use the address of a venue
+    pub fn uses_address(&self) -> bool {
+        return *self != MapProvider::Osm;
+    }
+
+    /// The link provider for this map app
+    pub fn link_provider(&self) -> &'static dyn MapLinkProvider {
+        return match self {
+            MapProvider::Google => &GoogleMaps,
+            MapProvider::Apple => &AppleMaps,
+            MapProvider::Osm => &OpenStreetMap,
+            MapProvider::Geo => &GeoUri,
+        };
+    }
+}
+
+impl FromStr for MapProvider {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s {
+            "google" => Ok(MapProvider::Google),
+            "apple" => Ok(MapProvider::Apple),
+            "osm" => Ok(MapProvider::Osm),
+            "geo" => Ok(MapProvider::Geo),
+            _ => Err(format!(
+                "Unknown map provider `{s}`, expected one of google, apple, osm or geo"
+            )),
+        };
+    }
+}
+
+/// How to travel to an event
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize, ToSchema,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum TravelMode {
+    Driving,
+    Transit,
+    Walking,
+    Cycling,
+}
+
+impl FromStr for TravelMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s {
+            "driving" => Ok(TravelMode::Driving),
+            "transit" => Ok(TravelMode::Transit),
+            "walking" => Ok(TravelMode::Walking),
+            "cycling" => Ok(TravelMode::Cycling),
+            _ => Err(format!(
+                "Unknown travel mode `{s}`, expected one of driving, transit, walking or cycling"
+            )),
+        };
+    }
+}
+
+/// Query parameters for choosing the map links of events
+#[derive(Debug, Clone, Default, Deserialize, IntoParams)]
+pub struct MapLinkQueryParams {
+    /// Map app to link to. Defaults to the provider configured on the server
+    pub map_provider: Option<MapProvider>,
+    /// How to travel to events. Defaults to the default mode of the map app
+    pub travel_mode: Option<TravelMode>,
+}
+
+impl MapLinkQueryParams {
+    /// Set the map link of every event in `res`, using `default_provider` if no provider was
+    /// requested
+    pub fn generate_map_urls(&self, res: &mut GQLResponse, default_provider: MapProvider) {
+        let provider = self.provider(default_provider);
+        res.generate_map_urls(provider.link_provider(), self.travel_mode);
+    }
+
+    /// Map app to link to, `default_provider` if no provider was requested
+    pub fn provider(&self, default_provider: MapProvider) -> MapProvider {
+        return self.map_provider.unwrap_or(default_provider);
+    }
+}
+
+/// Google Maps directions
+pub struct GoogleMaps;
+
+impl MapLinkProvider for GoogleMaps {
+    fn directions_url(&self, venue: &Venue, mode: Option<TravelMode>) -> String {
+        let mut url = format!(
+            "https://www.google.com/maps/dir/?api=1&destination={dest}",
+            dest = encode(&venue.destination())
+        );
+        if let Some(mode) = mode {
+            let mode = match mode {
+                TravelMode::Driving => "driving",
+                TravelMode::Transit => "transit",
+                TravelMode::Walking => "walking",
+                TravelMode::Cycling => "bicycling",
+            };
+            url.push_str(&format!("&travelmode={mode}"));
+        }
+        return url;
+    }
+}
+
+/// Apple Maps directions. Apple Maps links have no cycling mode, so cycling directions fall back
+/// to the mode chosen in the app
+pub struct AppleMaps;
+
+impl MapLinkProvider for AppleMaps {
+    fn directions_url(&self, venue: &Venue, mode: Option<TravelMode>) -> String {
+        let mut url = format!(
+            "https://maps.apple.com/?daddr={dest}",
+            dest = encode(&venue.destination())
+        );
+        let flag = match mode {
+            Some(TravelMode::Driving) => Some("d"),
+            Some(TravelMode::Transit) => Some("r"),
+            Some(TravelMode::Walking) => Some("w"),
+            Some(TravelMode::Cycling) | None => None,
+        };
+        if let Some(flag) = flag {
+            url.push_str(&format!("&dirflg={flag}"));
+        }
+        return url;
+    }
+}
+
+/// OpenStreetMap directions, routed by the OSRM instances of FOSSGIS. Routes only take
+/// coordinates, so the address of the venue is not used
+pub struct OpenStreetMap;
+
+impl MapLinkProvider for OpenStreetMap {
+    fn directions_url(&self, venue: &Venue, mode: Option<TravelMode>) -> String {
+        // Leave the start of the route empty, so the user can fill it in
+        let mut url = format!(
+            "https://www.openstreetmap.org/directions?route={route}",
+            route = encode(&format!(";{venue}"))
+        );
+        // OpenStreetMap has no transit routing
+        let engine = match mode {
+            Some(TravelMode::Driving) => Some("fossgis_osrm_car"),
+            Some(TravelMode::Walking) => Some("fossgis_osrm_foot"),
+            Some(TravelMode::Cycling) => Some("fossgis_osrm_bike"),
+            Some(TravelMode::Transit) | None => None,
+        };
+        if let Some(engine) = engine {
+            url.push_str(&format!("&engine={engine}"));
+        }
+        return url;
+    }
+}
+
+/// `geo:` URI of the venue, with the address as the search query if known. Travel mode is
+/// chosen in the map app
+pub struct GeoUri;
+
+impl MapLinkProvider for GeoUri {
+    fn directions_url(&self, venue: &Venue, _mode: Option<TravelMode>) -> String {
+        return match &venue.formatted_address {
+            Some(address) => format!("geo:{venue}?q={}", encode(address)),
+            None => format!("geo:{venue}"),
+        };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    /// Every provider links to the venue, with the travel mode if supported
+    fn can_generate_map_links() {
+        let venue = Venue {
+            lat: 43.6453,
+            lon: -79.3806,
+            ..Default::default()
+        };
+        let link = |provider: MapProvider, mode: Option<TravelMode>| {
+            provider.link_provider().directions_url(&venue, mode)
+        };
+        assert_eq!(
+            link(MapProvider::Google, Some(TravelMode::Cycling)),
+            "https://www.google.com/maps/dir/?api=1&destination=43.6453%2C-79.3806&travelmode=bicycling"
+        );
+        assert_eq!(
+            link(MapProvider::Apple, Some(TravelMode::Transit)),
+            "https://maps.apple.com/?daddr=43.6453%2C-79.3806&dirflg=r"
+        );
+        assert_eq!(
+            link(MapProvider::Apple, Some(TravelMode::Cycling)),
+            "https://maps.apple.com/?daddr=43.6453%2C-79.3806"
+        );
+        assert_eq!(
+            link(MapProvider::Osm, Some(TravelMode::Walking)),
+            "https://www.openstreetmap.org/directions?route=%3B43.6453%2C-79.3806&engine=fossgis_osrm_foot"
+        );
+        assert_eq!(
+            link(MapProvider::Osm, Some(TravelMode::Transit)),
+            "https://www.openstreetmap.org/directions?route=%3B43.6453%2C-79.3806"
+        );
+        assert_eq!(
+            link(MapProvider::Geo, Some(TravelMode::Driving)),
+            "geo:43.6453,-79.3806"
+        );
+
+        let venue = Venue {
+            formatted_address: Some("65 Front St W, Toronto, ON M5J 1E6".into()),
+            ..venue
+        };
+        assert_eq!(
+            MapProvider::Google
+                .link_provider()
+                .directions_url(&venue, None),
+            "https://www.google.com/maps/dir/?api=1&destination=65%20Front%20St%20W%2C%20Toronto%2C%20ON%20M5J%201E6"
+        );
+        assert_eq!(
+            MapProvider::Geo
+                .link_provider()
+                .directions_url(&venue, None),
+            "geo:43.6453,-79.3806?q=65%20Front%20St%20W%2C%20Toronto%2C%20ON%20M5J%201E6"
+        );
+        assert_eq!("osm".parse(), Ok(MapProvider::Osm));
+        assert!("bing".parse::<MapProvider>().is_err());
+    }
+}
@@ -0,0 +1,239 @@
+//! Canonical model of Meetup events, independent of the request that returned them
+
+use crate::meetup::query::common::EventType;
+use crate::meetup::query::request::{event, gql2, gql2_v2};
+use crate::utils::open_enum_schema;
+use serde::{Deserialize, Serialize};
+use utoipa::openapi::{RefOr, Schema};
+use utoipa::{PartialSchema, ToSchema};
+
+/// A single Meetup event
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Event {
+    pub id: String,
+    pub title: String,
+    /// Markdown description, if the request returned one
+    pub description: Option<String>,
+    /// Start of the event, in RFC 3339
+    pub date_time: String,
+    /// End of the event, in RFC 3339
+    pub end_time: Option<String>,
+    pub event_url: String,
+    pub event_type: EventType,
+    /// Whether people can RSVP, if the request returned it
+    pub rsvp_state: Option<RsvpState>,
+    /// Number of people going
+    pub going: i64,
+    pub venue: Option<Venue>,
+    pub group: Group,
+}
+
+/// Where an event takes place
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Venue {
+    pub name: String,
+    /// Street address, if known
+    pub address: Option<String>,
+    /// Full address formatted for the country of the venue, if known
+    pub formatted_address: Option<String>,
+    pub city: String,
+    pub state: String,
+    pub country: String,
+    /// Latitude and longitude, if known
+    pub coordinates: Option<(f64, f64)>,
+}
+
+/// The group hosting an event
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Group {
+    pub id: String,
+    pub name: String,
+    pub urlname: String,
+    /// IANA time zone of the group
+    pub timezone: String,
+}
+
+/// Whether people can RSVP to an event
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RsvpState {
+    #[default]
+    JoinOpen,
+    Closed,
+    JoinApproval,
+    NotOpenYet,
+    /// A state Meetup added that we don't know about yet
+    #[serde(untagged)]
+    Other(String),
+}
+
+impl PartialSchema for RsvpState {
+    fn schema() -> RefOr<Schema> {
+        return open_enum_schema(&["JOIN_OPEN", "CLOSED", "JOIN_APPROVAL", "NOT_OPEN_YET"]);
+    }
+}
+
+impl ToSchema for RsvpState {}
+
+impl Venue {
+    /// Human readable location of this venue
+    pub fn location(&self) -> String {
+        if let Some(address) = &self.formatted_address {
+            return format!("{}, {}", self.name, address);
+        }
+        return [
+            Some(&self.name),
+            self.address.as_ref(),
+            Some(&self.city),
+            Some(&self.state),
+            Some(&self.country),
+        ]
+        .into_iter()
+        .flatten()
+        .filter(|part| !part.is_empty())
+        .map(|part| part.as_str())
+        .collect::<Vec<&str>>()
+        .join(", ");
+    }
+}
+
+impl From<&gql2::Node> for Event {
+    fn from(node: &gql2::Node) -> Self {
+        return Self {
+            id: node.id.clone(),
+            title: node.title.clone(),
+            description: Some(node.description.clone()),
+            date_time: node.date_time.clone(),
+            end_time: node.end_time.clone(),
+            event_url: node.event_url.clone(),
+            event_type: node.event_type.clone(),
+            rsvp_state: Some(node.rsvp_state.clone()),
+            going: node.rsvps.total_count,
+            venue: node.venue.as_ref().map(Venue::from),
+            group: Group::from(&node.group),
+        };
+    }
+}
+
+impl From<&event::Event> for Event {
+    fn from(event: &event::Event) -> Self {
+        return Self {
+            id: event.id.clone(),
+            title: event.title.clone(),
+            description: event.description.clone(),
+            date_time: event.date_time.clone(),
+            end_time: event.end_time.clone(),
+            event_url: event.event_url.clone(),
+            event_type: event.event_type.clone(),
+            rsvp_state: Some(event.rsvp_state.clone()),
+            going: event.rsvps.total_count,
+            venue: event.venue.as_ref().map(Venue::from),
+            group: Group::from(&event.group),
+        };
+    }
+}
+
+impl From<&gql2::Venue> for Venue {
+    fn from(venue: &gql2::Venue) -> Self {
+        return Self {
+            name: venue.name.clone(),
+            address: venue.address.clone(),
+            formatted_address: venue.formatted_address.clone(),
+            city: venue.city.clone(),
+            state: venue.state.clone(),
+            country: venue.country.clone(),
+            coordinates: Some((venue.lat, venue.lon)),
+        };
+    }
+}
+
+impl From<&gql2::Group> for Group {
+    fn from(group: &gql2::Group) -> Self {
+        return Self {
+            id: group.id.clone(),
+            name: group.name.clone(),
+            urlname: group.urlname.clone(),
+            timezone: group.timezone.clone(),
+        };
+    }
+}
+
+impl From<&gql2_v2::Event> for Event {
+    fn from(event: &gql2_v2::Event) -> Self {
+        return Self {
+            id: event.id.clone(),
+            title: event.title.clone(),
+            description: None,
+            date_time: event.date_time.clone(),
+            // Meetup returns an empty end time for events without one
+            end_time: (!event.end_time.is_empty()).then(|| event.end_time.clone()),
+            event_url: event.event_url.clone(),
+            event_type: event.event_type.clone(),
+            // The RSVP feed doesn't return whether people can RSVP
+            rsvp_state: None,
+            going: event.going.total_count,
+            venue: Some(Venue::from(&event.venue)),
+            group: Group::from(&event.group),
+        };
+    }
+}
+
+impl From<&gql2_v2::Venue> for Venue {
+    fn from(venue: &gql2_v2::Venue) -> Self {
+        return Self {
+            name: venue.name.clone(),
+            address: Some(venue.address.clone()),
+            formatted_address: None,
+            city: venue.city.clone(),
+            state: venue.state.clone(),
+            country: venue.country.clone(),
+            coordinates: None,
+        };
+    }
+}
+
+impl From<&event::Group> for Group {
+    fn from(group: &event::Group) -> Self {
+        return Self {
+            id: group.id.clone(),
+            name: group.name.clone(),
+            urlname: group.urlname.clone(),
+            timezone: group.timezone.clone(),
+        };
+    }
+}
+
+impl From<&gql2_v2::Group> for Group {
+    fn from(group: &gql2_v2::Group) -> Self {
+        return Self {
+            id: group.id.clone(),
+            name: group.name.clone(),
+            urlname: group.urlname.clone(),
+            timezone: group.timezone.clone(),
+        };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    /// RSVP states are read from Meetup's names, keeping states we don't know about
+    fn can_parse_rsvp_state() {
+        let states: Vec<RsvpState> =
+            serde_json::from_str(r#"["JOIN_OPEN", "NOT_OPEN_YET", "SOLD_OUT"]"#).unwrap();
+        assert_eq!(
+            states,
+            vec![
+                RsvpState::JoinOpen,
+                RsvpState::NotOpenYet,
+                RsvpState::Other("SOLD_OUT".into())
+            ]
+        );
+        assert_eq!(
+            serde_json::to_string(&RsvpState::Other("SOLD_OUT".into())).unwrap(),
+            r#""SOLD_OUT""#
+        );
+    }
+}
@@ -1,25 +1,89 @@
 //! Common types for request bodies to Meetup api
+use crate::utils::open_enum_schema;
 use serde::{Deserialize, Serialize};
 use std::fmt::Display;
+use utoipa::openapi::{RefOr, Schema};
+use utoipa::{PartialSchema, ToSchema};
 
 /// types of events a meetup can be
-#[allow(non_camel_case_types)]
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum EventType {
+    /// In person
     #[default]
-    physical,
-    online,
+    Physical,
+    Online,
+    /// In person, and streamed online
+    Hybrid,
+    /// An event type Meetup added that we don't know about yet
+    #[serde(untagged)]
+    Other(String),
 }
 
 impl Display for EventType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            EventType::physical => write!(f, "PHYSICAL"),
-            EventType::online => write!(f, "ONLINE"),
+            EventType::Physical => write!(f, "PHYSICAL"),
+            EventType::Online => write!(f, "ONLINE"),
+            EventType::Hybrid => write!(f, "HYBRID"),
+            EventType::Other(event_type) => write!(f, "{event_type}"),
         }
     }
 }
 
+impl PartialSchema for EventType {
+    fn schema() -> RefOr<Schema> {
+        return open_enum_schema(&["PHYSICAL", "ONLINE", "HYBRID"]);
+    }
+}
+
+impl ToSchema for EventType {}
+
+/// Fields Meetup can sort events by
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub enum SortField {
+    #[default]
+    #[serde(rename = "RELEVANCE")]
+    Relevance,
+    /// Start time of the event
+    #[serde(rename = "DATETIME")]
+    DateTime,
+    /// Distance from the searched location
+    #[serde(rename = "DISTANCE")]
+    Distance,
+    /// A sort field Meetup added that we don't know about yet
+    #[serde(untagged)]
+    Other(String),
+}
+
+impl PartialSchema for SortField {
+    fn schema() -> RefOr<Schema> {
+        return open_enum_schema(&["RELEVANCE", "DATETIME", "DISTANCE"]);
+    }
+}
+
+impl ToSchema for SortField {}
+
+/// Search indexes Meetup can look events up in
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub enum IndexAlias {
+    /// Popular events near the searched location
+    #[default]
+    #[serde(rename = "popular_events_nearby_current")]
+    PopularEventsNearbyCurrent,
+    /// An index Meetup added that we don't know about yet
+    #[serde(untagged)]
+    Other(String),
+}
+
+impl PartialSchema for IndexAlias {
+    fn schema() -> RefOr<Schema> {
+        return open_enum_schema(&["popular_events_nearby_current"]);
+    }
+}
+
+impl ToSchema for IndexAlias {}
+
 /// Types of oepration names for requests to the Meetup api gql2
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[non_exhaustive]
@@ -67,3 +131,32 @@ pub struct PersistedQuery {
 //         }
 //     }
 // }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    /// Enums use Meetup's names, keeping values we don't know about
+    fn can_parse_enums() {
+        let event_types: Vec<EventType> =
+            serde_json::from_str(r#"["PHYSICAL", "HYBRID", "IN_SPACE"]"#).unwrap();
+        assert_eq!(
+            event_types,
+            vec![
+                EventType::Physical,
+                EventType::Hybrid,
+                EventType::Other("IN_SPACE".into())
+            ]
+        );
+        assert_eq!(
+            serde_json::to_string(&SortField::DateTime).unwrap(),
+            r#""DATETIME""#
+        );
+        assert_eq!(
+            serde_json::from_str::<IndexAlias>(r#""popular_events_nearby_current""#).unwrap(),
+            IndexAlias::PopularEventsNearbyCurrent
+        );
+        assert_eq!(EventType::Other("IN_SPACE".into()).to_string(), "IN_SPACE");
+    }
+}
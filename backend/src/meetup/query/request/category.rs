@@ -0,0 +1,106 @@
+//! Topic categories Meetup organizes events by
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::OnceCell;
+use tracing::info;
+use utoipa::ToSchema;
+
+use super::post;
+
+/// Request for all topic categories
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CategoriesRequest {
+    operation_name: String,
+    query: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct CategoriesResponse {
+    data: Option<CategoriesData>,
+    errors: Option<Vec<Value>>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct CategoriesData {
+    topic_categories: Vec<Category>,
+}
+
+/// A topic category, ie `Technology` or `Sports & Fitness`
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Category {
+    /// ID to filter events by with `category_id`
+    pub id: String,
+    pub name: String,
+    /// Name of the category in URLs on meetup.com
+    pub urlkey: String,
+}
+
+impl Default for CategoriesRequest {
+    fn default() -> Self {
+        return Self {
+            operation_name: "topicCategories".to_string(),
+            query: "query topicCategories { topicCategories { id name urlkey } }".to_string(),
+        };
+    }
+}
+
+impl CategoriesRequest {
+    /// Send the API request
+    pub async fn fetch(&self) -> Result<Vec<Category>> {
+        let response = post::<Self, CategoriesResponse>(self).await?;
+        let Some(data) = response.data else {
+            return Err(anyhow!(
+                serde_json::to_string(&response.errors).unwrap_or_default()
+            ));
+        };
+        return Ok(data.topic_categories);
+    }
+}
+
+/// Topic categories, fetched from Meetup the first time they are needed. Categories rarely
+/// change, so they are kept until the server restarts
+#[derive(Default)]
+pub struct CategoryCache {
+    categories: OnceCell<Vec<Category>>,
+}
+
+impl CategoryCache {
+    /// Get all topic categories. A failed fetch is not cached, and is retried by the next call
+    pub async fn get(&self) -> Result<&[Category]> {
+        let categories = self
+            .categories
+            .get_or_try_init(|| async {
+                let categories = CategoriesRequest::default().fetch().await?;
+                info!("Fetched {} topic categories", categories.len());
+                return Ok::<_, anyhow::Error>(categories);
+            })
+            .await?;
+        return Ok(categories);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    /// Categories are read from the `topicCategories` query
+    fn can_parse_categories() {
+        let response: CategoriesResponse = serde_json::from_str(
+            r#"{"data":{"topicCategories":[{"id":"546","name":"Technology","urlkey":"tech"}]}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            response.data.unwrap().topic_categories,
+            vec![Category {
+                id: "546".into(),
+                name: "Technology".into(),
+                urlkey: "tech".into(),
+            }]
+        );
+    }
+}
@@ -0,0 +1,147 @@
+//! Single events looked up by ID
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::gql2::{Rsvps, Venue};
+use super::post;
+use crate::meetup::model::RsvpState;
+use crate::meetup::query::common::EventType;
+
+/// Request for a single event by ID
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct EventRequest {
+    operation_name: String,
+    query: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct EventResponse {
+    data: Option<EventData>,
+    errors: Option<Vec<Value>>,
+}
+
+#[derive(Deserialize, Debug)]
+struct EventData {
+    event: Option<Event>,
+}
+
+/// An event returned by an [EventRequest]
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Event {
+    pub id: String,
+    pub title: String,
+    pub description: Option<String>,
+    pub date_time: String,
+    pub end_time: Option<String>,
+    pub event_url: String,
+    pub event_type: EventType,
+    pub rsvp_state: RsvpState,
+    pub rsvps: Rsvps,
+    pub venue: Option<Venue>,
+    pub group: Group,
+}
+
+/// The group hosting an [Event]
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Group {
+    pub id: String,
+    pub name: String,
+    pub urlname: String,
+    pub timezone: String,
+}
+
+/// Whether `id` is an event ID that can be put in a query. Meetup event IDs are alphanumeric
+pub fn is_event_id(id: &str) -> bool {
+    return !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric());
+}
+
+impl EventRequest {
+    /// Build a request for the event `id`. None if `id` isn't an event ID
+    pub fn new(id: &str) -> Option<Self> {
+        if !is_event_id(id) {
+            return None;
+        }
+        return Some(Self {
+            operation_name: "eventById".to_string(),
+            query: format!(
+                "query eventById {{ event(id: \"{id}\") {{ id title description dateTime endTime \
+                 eventUrl eventType rsvpState rsvps {{ totalCount }} venue {{ id name lat lon \
+                 city state country address postalCode }} group {{ id name urlname timezone }} }} }}"
+            ),
+        });
+    }
+
+    /// Send the API request, returning the event if it exists
+    pub async fn fetch(&self) -> Result<Option<Event>> {
+        return parse_response(post::<Self, EventResponse>(self).await?);
+    }
+}
+
+/// The event in `response`, with its venue address formatted
+fn parse_response(response: EventResponse) -> Result<Option<Event>> {
+    let Some(data) = response.data else {
+        return Err(anyhow!(
+            serde_json::to_string(&response.errors).unwrap_or_default()
+        ));
+    };
+    let Some(mut event) = data.event else {
+        return Ok(None);
+    };
+    if let Some(venue) = event.venue.as_mut() {
+        venue.address = venue.address.take().filter(|a| !a.trim().is_empty());
+        venue.format_address();
+    }
+    return Ok(Some(event));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    /// Only alphanumeric IDs are put in the query
+    fn can_build_request() {
+        let request = EventRequest::new("304823122").unwrap();
+        assert!(request
+            .query
+            .starts_with("query eventById { event(id: \"304823122\") { id title"));
+        assert!(EventRequest::new("\"} evil").is_none());
+        assert!(EventRequest::new("").is_none());
+    }
+
+    #[test]
+    /// Events are parsed with their venue address formatted, and missing events are None
+    fn can_parse_response() {
+        let response: EventResponse = serde_json::from_str(
+            r#"{"data": {"event": {
+                "id": "1", "title": "Rust Meetup", "description": "Talks",
+                "dateTime": "2024-12-01T18:00:00-05:00", "endTime": null,
+                "eventUrl": "https://www.meetup.com/rust/events/1", "eventType": "PHYSICAL",
+                "rsvpState": "JOIN_OPEN", "rsvps": {"totalCount": 12},
+                "venue": {"id": "2", "name": "Union", "lat": 43.6, "lon": -79.4,
+                    "city": "Toronto", "state": "ON", "country": "ca",
+                    "address": "65 Front St W", "postalCode": "M5J 1E6"},
+                "group": {"id": "3", "name": "Rust", "urlname": "rust",
+                    "timezone": "America/Toronto"}
+            }}}"#,
+        )
+        .unwrap();
+        let event = parse_response(response).unwrap().unwrap();
+        assert_eq!(event.rsvps.total_count, 12);
+        assert_eq!(
+            event.venue.unwrap().formatted_address.as_deref(),
+            Some("65 Front St W, Toronto, ON M5J 1E6")
+        );
+
+        let missing: EventResponse = serde_json::from_str(r#"{"data": {"event": null}}"#).unwrap();
+        assert_eq!(parse_response(missing).unwrap(), None);
+        let failed: EventResponse =
+            serde_json::from_str(r#"{"data": null, "errors": [{"message": "Not found"}]}"#)
+                .unwrap();
+        assert!(parse_response(failed).is_err());
+    }
+}
@@ -1,10 +1,16 @@
 //! Types for meetup GQL2 API
-use std::cmp::Ordering;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt::Display;
+use std::str::FromStr;
 
-use crate::meetup::query::common::EventType;
+use crate::map_link::{MapLinkProvider, TravelMode};
+use crate::meetup::model::RsvpState;
+use crate::meetup::query::common::{EventType, IndexAlias, SortField};
 use crate::meetup::query::common::{Extensions, OperationName2, PersistedQuery};
-use crate::utils::now;
+use crate::sanitize::HtmlSanitizer;
+use crate::time_filter::TimeFilter;
+use crate::utils::{haversine_km, markdown_to_text, now, summarize};
 use anyhow::anyhow;
 use anyhow::Result;
 use bon::bon;
@@ -15,11 +21,13 @@ use serde::Deserialize;
 use serde::Serialize;
 use serde_json::Value;
 use tracing::{debug, error};
-use urlencoding::encode;
 use utoipa::ToSchema;
 
 use super::post;
 
+/// Maximum number of characters in the summary of an event description
+const DESCRIPTION_SUMMARY_LENGTH: usize = 200;
+
 /// Represents the body of an API request to the Meetup graphql API
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
@@ -73,17 +81,18 @@ impl Default for Variables {
             lat: 43.7400016784668,
             lon: -79.36000061035156,
             city: "Toronto".into(),
-            sort_field: "RELEVANCE".into(),
+            sort_field: SortField::default(),
             start_date_range: now(),
             end_date_range: None,
             series_start_date: Utc::now().format("%Y-%m-%d").to_string(),
             after: None,
-            event_type: EventType::default().to_string(),
-            index_alias: "popular_events_nearby_current".into(),
+            event_type: EventType::default(),
+            index_alias: IndexAlias::default(),
             do_consolidate_events: true,
             do_promote_paypal_events: false,
             number_of_events_for_series: 5,
             query: None,
+            category_id: None,
         }
     }
 }
@@ -144,10 +153,87 @@ impl Edge {
         self.node.date_time = date.format("%a %m-%d %I:%M%P").to_string();
     }
 
-    /// Parses the event descriptions as markdown
-    pub fn description_to_html(&mut self) {
-        let html = to_html(self.node.description.as_str()).clone();
-        self.node.description = html;
+    /// Parses the event descriptions as markdown, and cleans the resulting HTML with `sanitizer`
+    pub fn description_to_html(&mut self, sanitizer: &HtmlSanitizer) {
+        let html = to_html(self.node.description.as_str());
+        self.node.description = sanitizer.clean(&html);
+    }
+
+    /// Add the requested description `fields`, computed from the markdown description
+    pub fn add_description_fields(&mut self, fields: &[DescriptionField]) {
+        let node = &mut self.node;
+        let text = markdown_to_text(&node.description);
+        for field in fields {
+            match field {
+                DescriptionField::Text => node.description_text = Some(text.clone()),
+                DescriptionField::Summary => {
+                    node.description_summary = Some(summarize(&text, DESCRIPTION_SUMMARY_LENGTH))
+                }
+                DescriptionField::Markdown => {
+                    node.description_markdown = Some(node.description.clone())
+                }
+            }
+        }
+    }
+
+    /// Build a copy of this event for another occurrence in its series
+    fn occurrence(&self, occurrence: &Node2) -> Edge {
+        let mut edge = self.clone();
+        edge.node.id = occurrence.id.clone();
+        edge.node.date_time = occurrence.date_time.clone();
+        edge.node.is_attending = occurrence.is_attending;
+        edge.node.event_url = format!(
+            "https://www.meetup.com/{urlname}/events/{id}/",
+            urlname = occurrence.group.urlname,
+            id = occurrence.id
+        );
+        // RSVP count is only known for the event that was returned
+        edge.node.rsvps = Rsvps::default();
+        edge.node.series = None;
+        return edge;
+    }
+
+    /// Check if `other` is the same event as this one, cross posted by another group
+    ///
+    /// Events are considered the same if they are hosted by different groups, start at the same
+    /// time and have similar titles
+    fn is_cross_post_of(&self, other: &Edge) -> bool {
+        if self.node.group.id == other.node.group.id {
+            return false;
+        }
+        let (Ok(date), Ok(other_date)) = (
+            DateTime::parse_from_rfc3339(&self.node.date_time),
+            DateTime::parse_from_rfc3339(&other.node.date_time),
+        ) else {
+            return false;
+        };
+        return date == other_date && title_similarity(&self.node.title, &other.node.title) >= 0.8;
+    }
+
+    /// Add `other_queries` to the search queries this event matched
+    fn merge_matched_queries(&mut self, other_queries: Option<Vec<String>>) {
+        let Some(other_queries) = other_queries else {
+            return;
+        };
+        let queries = self.node.matched_queries.get_or_insert_with(Vec::new);
+        for query in other_queries {
+            if !queries.contains(&query) {
+                queries.push(query);
+            }
+        }
+    }
+
+    /// Merge a cross posted copy of this event into this event
+    fn merge_cross_post(&mut self, other: Edge) {
+        self.merge_matched_queries(other.node.matched_queries);
+        self.node.is_attending |= other.node.is_attending;
+        self.node.is_saved |= other.node.is_saved;
+        let groups = self.node.cross_posted_groups.get_or_insert_with(Vec::new);
+        if other.node.group.urlname != self.node.group.urlname
+            && !groups.iter().any(|g| g.urlname == other.node.group.urlname)
+        {
+            groups.push(other.node.group);
+        }
     }
 
     /// Populate `self.is_attending_str` based on `self.is_attending`
@@ -162,7 +248,7 @@ impl Edge {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Variables {
     /// Number of results to return
@@ -170,21 +256,23 @@ pub struct Variables {
     pub first: i32,
     pub lat: f64,
     pub lon: f64,
-    pub sort_field: String,
+    pub sort_field: SortField,
     pub start_date_range: String,
     pub end_date_range: Option<String>,
     pub series_start_date: String,
     /// The after cursor
     pub after: Option<String>,
     /// Type of event
-    pub event_type: String,
-    pub index_alias: String,
+    pub event_type: EventType,
+    pub index_alias: IndexAlias,
     pub do_consolidate_events: bool,
     pub do_promote_paypal_events: bool,
     pub city: String,
     pub number_of_events_for_series: i32,
     /// Search query. Only applicable with SearchRequest operation `eventSearchWithSeries`
     pub query: Option<String>,
+    /// Only return events in this topic category
+    pub category_id: Option<String>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
@@ -240,11 +328,205 @@ impl GQLResponse {
         });
     }
 
+    /// Add the events of `other` to the events in this response
+    pub fn merge(&mut self, other: GQLResponse) {
+        let Some(other) = other.data else {
+            return;
+        };
+        let result = &mut self.data.get_or_insert_with(Default::default).result;
+        result.total_count += other.result.total_count;
+        result.edges.extend(other.result.edges);
+    }
+
+    /// Remove duplicated events, keeping the first occurrence of each event
+    pub fn dedup(&mut self, mode: &DedupMode) {
+        let result = &mut self.data.as_mut().unwrap().result;
+        let edges = std::mem::take(&mut result.edges);
+        let mut seen: HashMap<String, usize> = HashMap::new();
+        for edge in edges {
+            if let Some(&i) = seen.get(&edge.node.id) {
+                result.edges[i].merge_matched_queries(edge.node.matched_queries);
+                continue;
+            }
+            if *mode == DedupMode::Fuzzy {
+                if let Some(existing) = result.edges.iter_mut().find(|e| e.is_cross_post_of(&edge))
+                {
+                    existing.merge_cross_post(edge);
+                    continue;
+                }
+            }
+            seen.insert(edge.node.id.clone(), result.edges.len());
+            result.edges.push(edge);
+        }
+    }
+
+    /// Remove events whose start date can't be parsed, so formatting the rest can't fail
+    pub fn skip_invalid_start_dates(&mut self) {
+        if let Some(data) = self.data.as_mut() {
+            data.result.edges.retain(|edge| {
+                if DateTime::parse_from_rfc3339(&edge.node.date_time).is_ok() {
+                    return true;
+                }
+                error!(
+                    "Skipping event {} with invalid start date `{}`",
+                    edge.node.id, edge.node.date_time
+                );
+                return false;
+            });
+        }
+    }
+
+    /// Record `query` as a search query that matched every event in this response
+    pub fn tag_matched_query(&mut self, query: &str) {
+        if let Some(data) = self.data.as_mut() {
+            for edge in data.result.edges.iter_mut() {
+                edge.node.matched_queries = Some(vec![query.to_string()]);
+            }
+        }
+    }
+
+    /// Sort events matching the most search queries first. Events matching the same number of
+    /// queries keep their current order
+    pub fn sort_by_matched_queries(&mut self) {
+        self.data
+            .as_mut()
+            .unwrap()
+            .result
+            .edges
+            .sort_by_key(|e| Reverse(e.node.matched_queries.as_ref().map_or(0, Vec::len)));
+    }
+
+    /// Set the distance from `lat`, `lon` to the venue of every in person event. Online events
+    /// don't have a distance
+    pub fn compute_distances(&mut self, lat: f64, lon: f64) {
+        for edge in &mut self.data.as_mut().unwrap().result.edges {
+            edge.node.distance_km = match &edge.node.venue {
+                Some(venue) if !edge.node.is_online => {
+                    Some(haversine_km((lat, lon), (venue.lat, venue.lon)))
+                }
+                _ => None,
+            };
+        }
+    }
+
+    /// Remove in person events more than `max_km` away, or without a known distance. Online
+    /// events are kept, since they can be attended from anywhere
+    pub fn filter_by_distance(&mut self, max_km: f64) {
+        self.data
+            .as_mut()
+            .unwrap()
+            .result
+            .edges
+            .retain(|e| e.node.is_online || e.node.distance_km.is_some_and(|d| d <= max_km));
+    }
+
+    /// Remove events that don't start on the days and times in `filter`
+    pub fn filter_by_time(&mut self, filter: &TimeFilter) {
+        if filter.is_empty() {
+            return;
+        }
+        self.data
+            .as_mut()
+            .unwrap()
+            .result
+            .edges
+            .retain(|e| filter.matches(&e.node.date_time, &e.node.group.timezone));
+    }
+
+    /// Sort in person events by placing the closest first. Events without a known distance
+    /// come after, followed by online events
+    pub fn sort_by_distance(&mut self) {
+        self.data.as_mut().unwrap().result.edges.sort_by(|a, b| {
+            let key = |e: &Edge| (e.node.is_online, e.node.distance_km.is_none());
+            return key(a).cmp(&key(b)).then_with(|| {
+                let (a, b) = (a.node.distance_km, b.node.distance_km);
+                a.unwrap_or_default().total_cmp(&b.unwrap_or_default())
+            });
+        });
+    }
+
+    /// Apply `mode` to every event that is part of a series
+    pub fn apply_series_mode(&mut self, mode: &SeriesMode) {
+        match mode {
+            SeriesMode::Nested => {}
+            SeriesMode::Collapse => self.collapse_series(),
+            SeriesMode::Expand => self.expand_series(),
+        }
+    }
+
+    /// Replace every event in a series with the next upcoming occurrence of that series, and drop
+    /// other occurrences of the same series
+    fn collapse_series(&mut self) {
+        let now = Utc::now();
+        let mut seen: HashSet<String> = HashSet::new();
+        self.data.as_mut().unwrap().result.edges.retain_mut(|edge| {
+            let Some(series) = edge.node.series.take() else {
+                return seen.insert(edge.node.id.clone());
+            };
+
+            let next = series
+                .events
+                .edges
+                .iter()
+                .filter_map(|e| {
+                    let date = DateTime::parse_from_rfc3339(&e.node.date_time).ok()?;
+                    return (date >= now).then_some((date, &e.node));
+                })
+                .min_by_key(|(date, _)| *date);
+            let current = DateTime::parse_from_rfc3339(&edge.node.date_time).ok();
+            if let Some((date, next)) = next {
+                if current.is_none_or(|current| current < now || date < current) {
+                    *edge = edge.occurrence(next);
+                }
+            }
+
+            let mut ids: Vec<String> = series.events.edges.into_iter().map(|e| e.node.id).collect();
+            ids.push(edge.node.id.clone());
+            let duplicate = ids.iter().any(|id| seen.contains(id));
+            seen.extend(ids);
+            return !duplicate;
+        });
+    }
+
+    /// Expand every event series into a separate event per occurrence
+    fn expand_series(&mut self) {
+        let edges = &mut self.data.as_mut().unwrap().result.edges;
+        let mut seen: HashSet<String> = edges.iter().map(|e| e.node.id.clone()).collect();
+        let mut occurrences = vec![];
+        for edge in edges.iter_mut() {
+            let Some(series) = edge.node.series.take() else {
+                continue;
+            };
+            for occurrence in series.events.edges {
+                if seen.insert(occurrence.node.id.clone()) {
+                    occurrences.push(edge.occurrence(&occurrence.node));
+                }
+            }
+        }
+        edges.append(&mut occurrences);
+    }
+
+    /// Add the requested description `fields` to every event. Must be called before formatting,
+    /// while the description is still markdown
+    pub fn add_description_fields(&mut self, fields: &[DescriptionField]) {
+        if fields.is_empty() {
+            return;
+        }
+        self.data
+            .as_mut()
+            .unwrap()
+            .result
+            .edges
+            .par_iter_mut()
+            .map(|edge| edge.add_description_fields(fields))
+            .for_each(drop);
+    }
+
     /// Format all events by doing the following:
-    /// - Compiles the description of the event to HTML
+    /// - Compiles the description of the event to HTML, cleaned with `sanitizer`
     /// - Formats the starting date of the meetup in a human readable format
     /// - Populates `is_attending_str` for all events
-    pub fn format(&mut self) {
+    pub fn format(&mut self, sanitizer: &HtmlSanitizer) {
         self.data
             .as_mut()
             .unwrap()
@@ -252,14 +534,15 @@ impl GQLResponse {
             .edges
             .par_iter_mut()
             .map(|edge| {
-                edge.description_to_html();
+                edge.description_to_html(sanitizer);
                 edge.format_start_date();
                 edge.is_attending_to_str();
             })
             .for_each(drop);
     }
 
-    pub fn generate_google_maps_url(&mut self) {
+    /// Set the map link of every event with a venue, using `provider` with travel `mode`
+    pub fn generate_map_urls(&mut self, provider: &dyn MapLinkProvider, mode: Option<TravelMode>) {
         self.data
             .as_mut()
             .unwrap()
@@ -267,17 +550,127 @@ impl GQLResponse {
             .edges
             .par_iter_mut()
             .map(|edge| {
-                if edge.node.venue.is_some() {
-                    edge.node.google_maps_url = Some(format!(
-                        "https://www.google.com/maps/dir/?api=1&destination={dest}",
-                        dest = encode(&edge.node.venue.clone().unwrap_or_default().to_string())
-                    ))
+                // Online events don't have a place to go to
+                if edge.node.is_online {
+                    return;
+                }
+                if let Some(venue) = &edge.node.venue {
+                    edge.node.map_url = Some(provider.directions_url(venue, mode));
                 }
             })
             .for_each(drop);
     }
 }
 
+#[cfg(test)]
+impl GQLResponse {
+    /// Build a response with an event for each of `nodes`
+    pub fn from_nodes(nodes: Vec<Node>) -> Self {
+        let total_count = nodes.len() as i64;
+        let edges = nodes
+            .into_iter()
+            .map(|node| Edge {
+                node,
+                ..Default::default()
+            })
+            .collect();
+        return Self {
+            data: Some(GQLData {
+                result: MeetupResult {
+                    total_count,
+                    edges,
+                    ..Default::default()
+                },
+            }),
+            errors: None,
+        };
+    }
+}
+
+#[cfg(test)]
+impl Node {
+    /// An event with `id` starting at `date_time`, and every other field empty
+    pub fn at(id: &str, date_time: &str) -> Self {
+        return Self {
+            id: id.into(),
+            date_time: date_time.into(),
+            ..Default::default()
+        };
+    }
+}
+
+/// Similarity of two event titles, between 0 and 1
+///
+/// Calculated as the ratio of shared words to total words, ignoring case and punctuation
+fn title_similarity(a: &str, b: &str) -> f64 {
+    let words = |title: &str| -> HashSet<String> {
+        return title
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty())
+            .map(|w| w.to_lowercase())
+            .collect();
+    };
+    let (a, b) = (words(a), words(b));
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+    return a.intersection(&b).count() as f64 / a.union(&b).count() as f64;
+}
+
+/// How duplicated events are detected
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DedupMode {
+    /// Events with the same ID are duplicates
+    #[default]
+    Id,
+    /// Events with the same ID, or events starting at the same time with similar titles are
+    /// duplicates. Cross posted events are merged into one, listing all groups hosting the event
+    Fuzzy,
+}
+
+/// Extra forms of the event description, only returned when requested
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+pub enum DescriptionField {
+    /// `descriptionText`, the description with all markdown formatting removed
+    #[serde(rename = "descriptionText")]
+    Text,
+    /// `descriptionSummary`, the start of the plain text description, cut at a sentence
+    #[serde(rename = "descriptionSummary")]
+    Summary,
+    /// `descriptionMarkdown`, the original markdown description
+    #[serde(rename = "descriptionMarkdown")]
+    Markdown,
+}
+
+impl FromStr for DescriptionField {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        return match s {
+            "descriptionText" => Ok(DescriptionField::Text),
+            "descriptionSummary" => Ok(DescriptionField::Summary),
+            "descriptionMarkdown" => Ok(DescriptionField::Markdown),
+            _ => Err(format!(
+                "Unknown field `{s}`, expected one of descriptionText, descriptionSummary or descriptionMarkdown"
+            )),
+        };
+    }
+}
+
+/// How events that are part of a series are returned
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SeriesMode {
+    /// Return the series as is, nested in each event
+    #[default]
+    Nested,
+    /// Only return the next upcoming occurrence of each series
+    Collapse,
+    /// Return each occurrence of a series as a separate event
+    Expand,
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct GQLData {
@@ -311,9 +704,20 @@ pub struct Edge {
 #[serde(rename_all = "camelCase")]
 pub struct Node {
     pub date_time: String,
-    pub google_maps_url: Option<String>,
+    pub end_time: Option<String>,
+    /// Link to directions to the venue
+    pub map_url: Option<String>,
     pub description: String,
-    pub event_type: String,
+    /// Description without formatting. Only set when requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description_text: Option<String>,
+    /// Start of the description without formatting, cut at a sentence. Only set when requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description_summary: Option<String>,
+    /// Original markdown description. Only set when requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description_markdown: Option<String>,
+    pub event_type: EventType,
     pub event_url: String,
     pub featured_event_photo: Option<FeaturedEventPhoto>,
     pub fee_settings: Option<FeeSettings>,
@@ -329,9 +733,19 @@ pub struct Node {
     pub rsvps: Rsvps,
     pub title: String,
     pub venue: Option<Venue>,
+    /// Distance to the venue in kilometres, when searching from a location. Not set for online
+    /// events
+    pub distance_km: Option<f64>,
+    /// Estimated minutes it takes to travel to the venue by each travel mode, when searching
+    /// from a location. Not set for online events
+    pub travel_minutes: Option<BTreeMap<TravelMode, u32>>,
     pub social_labels: Vec<Value>,
-    pub rsvp_state: String,
+    pub rsvp_state: RsvpState,
     pub series: Option<Series>,
+    /// Other groups this event has been cross posted by
+    pub cross_posted_groups: Option<Vec<Group>>,
+    /// Search queries this event matched, when searching with multiple queries
+    pub matched_queries: Option<Vec<String>>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
@@ -398,6 +812,51 @@ pub struct Venue {
     pub city: String,
     pub state: String,
     pub country: String,
+    /// Street address
+    pub address: Option<String>,
+    pub postal_code: Option<String>,
+    /// Full address, formatted by the conventions of the country of the venue
+    pub formatted_address: Option<String>,
+}
+
+impl Venue {
+    /// Set `formatted_address` from the street address of this venue. Not set if the street
+    /// address is unknown
+    pub fn format_address(&mut self) {
+        let Some(address) = &self.address else {
+            self.formatted_address = None;
+            return;
+        };
+        let postal_code = self.postal_code.as_deref().unwrap_or_default();
+        let join = |parts: &[&str], separator: &str| {
+            return parts
+                .iter()
+                .map(|p| p.trim())
+                .filter(|p| !p.is_empty())
+                .collect::<Vec<&str>>()
+                .join(separator);
+        };
+        let locality = match self.country.to_lowercase().as_str() {
+            // City, state and postal code, ie `Toronto, ON M5J 1E6`
+            "us" | "ca" | "au" => {
+                join(&[&self.city, &join(&[&self.state, postal_code], " ")], ", ")
+            }
+            // Postal code after the city, ie `London SW1A 2AA`
+            "gb" | "ie" => join(&[&self.city, postal_code], " "),
+            // Postal code before the city, ie `10115 Berlin`
+            _ => join(&[postal_code, &self.city], " "),
+        };
+        self.formatted_address = Some(join(&[address, &locality], ", "));
+    }
+
+    /// Where to go to get to this venue. The formatted address if known, otherwise the
+    /// coordinates
+    pub fn destination(&self) -> String {
+        return self
+            .formatted_address
+            .clone()
+            .unwrap_or_else(|| self.to_string());
+    }
 }
 
 impl Display for Venue {
@@ -456,4 +915,299 @@ mod test {
             .operation_name(OperationName2::recommendedEventsWithSeries)
             .build();
     }
+
+    #[test]
+    /// Requested description fields are computed from the markdown description
+    fn can_add_description_fields() {
+        let mut edge = Edge {
+            node: Node {
+                description: "**Welcome!** Bring a laptop.\n\n- Talks\n- Pizza".into(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        edge.add_description_fields(&[DescriptionField::Text, DescriptionField::Summary]);
+        assert_eq!(
+            edge.node.description_text.as_deref(),
+            Some("Welcome! Bring a laptop.\n\n- Talks\n- Pizza")
+        );
+        assert_eq!(
+            edge.node.description_summary.as_deref(),
+            Some("Welcome! Bring a laptop. - Talks - Pizza")
+        );
+        assert_eq!(edge.node.description_markdown, None);
+    }
+
+    #[test]
+    /// Addresses are formatted by the conventions of the country of the venue
+    fn can_format_address() {
+        let address = |country: &str, city: &str, state: &str, postal_code: &str| {
+            let mut venue = Venue {
+                address: Some("1 Main St".into()),
+                postal_code: Some(postal_code.into()),
+                city: city.into(),
+                state: state.into(),
+                country: country.into(),
+                ..Default::default()
+            };
+            venue.format_address();
+            return venue.formatted_address.unwrap();
+        };
+        assert_eq!(
+            address("ca", "Toronto", "ON", "M5J 1E6"),
+            "1 Main St, Toronto, ON M5J 1E6"
+        );
+        assert_eq!(
+            address("gb", "London", "", "SW1A 2AA"),
+            "1 Main St, London SW1A 2AA"
+        );
+        assert_eq!(
+            address("de", "Berlin", "", "10115"),
+            "1 Main St, 10115 Berlin"
+        );
+        assert_eq!(address("us", "Seattle", "WA", ""), "1 Main St, Seattle, WA");
+
+        let mut venue = Venue {
+            lat: 43.6453,
+            lon: -79.3806,
+            ..Default::default()
+        };
+        venue.format_address();
+        assert_eq!(venue.destination(), "43.6453,-79.3806");
+    }
+
+    #[test]
+    /// Distance is computed for in person events only, and online events are kept when filtering
+    fn can_filter_by_distance() {
+        let event = |id: &str, venue: Option<(f64, f64)>, is_online: bool| Edge {
+            node: Node {
+                id: id.into(),
+                venue: venue.map(|(lat, lon)| Venue {
+                    lat,
+                    lon,
+                    ..Default::default()
+                }),
+                is_online,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut res = GQLResponse {
+            data: Some(GQLData {
+                result: MeetupResult {
+                    edges: vec![
+                        // Montreal
+                        event("montreal", Some((45.5019, -73.5674)), false),
+                        // Online events have the coordinates of the group
+                        event("online", Some((43.6532, -79.3832)), true),
+                        event("unknown", None, false),
+                        // Union Station
+                        event("union", Some((43.6453, -79.3806)), false),
+                    ],
+                    ..Default::default()
+                },
+            }),
+            ..Default::default()
+        };
+        // Toronto City Hall
+        res.compute_distances(43.6534, -79.3841);
+        res.sort_by_distance();
+        let edges = &res.data.as_ref().unwrap().result.edges;
+        let ids: Vec<&str> = edges.iter().map(|e| e.node.id.as_str()).collect();
+        assert_eq!(ids, ["union", "montreal", "unknown", "online"]);
+        assert!((edges[1].node.distance_km.unwrap() - 504.0).abs() < 5.0);
+        assert_eq!(edges[3].node.distance_km, None);
+
+        res.filter_by_distance(10.0);
+        let ids: Vec<&str> = res
+            .data
+            .as_ref()
+            .unwrap()
+            .result
+            .edges
+            .iter()
+            .map(|e| e.node.id.as_str())
+            .collect();
+        assert_eq!(ids, ["union", "online"]);
+    }
+
+    #[test]
+    /// Every occurrence of a series is returned as its own event
+    fn can_expand_series() {
+        let occurrence = |id: &str, date_time: &str, is_attending: bool| Edge2 {
+            node: Node2 {
+                id: id.into(),
+                date_time: date_time.into(),
+                is_attending,
+                group: Group2 {
+                    urlname: "rust-toronto".into(),
+                },
+            },
+        };
+        let mut res = GQLResponse {
+            data: Some(GQLData {
+                result: MeetupResult {
+                    edges: vec![Edge {
+                        node: Node {
+                            id: "1".into(),
+                            date_time: "2024-12-01T18:00:00-05:00".into(),
+                            series: Some(Series {
+                                events: Events {
+                                    edges: vec![
+                                        occurrence("1", "2024-12-01T18:00:00-05:00", false),
+                                        occurrence("2", "2024-12-08T18:00:00-05:00", true),
+                                    ],
+                                },
+                            }),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+            }),
+            errors: None,
+        };
+
+        res.apply_series_mode(&SeriesMode::Expand);
+        let edges = &res.data.unwrap().result.edges;
+        assert_eq!(edges.len(), 2);
+        assert!(edges.iter().all(|e| e.node.series.is_none()));
+        assert_eq!(edges[1].node.id, "2");
+        assert_eq!(edges[1].node.date_time, "2024-12-08T18:00:00-05:00");
+        assert!(edges[1].node.is_attending);
+        assert_eq!(
+            edges[1].node.event_url,
+            "https://www.meetup.com/rust-toronto/events/2/"
+        );
+    }
+
+    #[test]
+    /// Each series is replaced by its next upcoming occurrence, and other occurrences are dropped
+    fn can_collapse_series() {
+        let occurrence = |id: &str, date_time: &str| Edge2 {
+            node: Node2 {
+                id: id.into(),
+                date_time: date_time.into(),
+                is_attending: false,
+                group: Group2 {
+                    urlname: "rust-toronto".into(),
+                },
+            },
+        };
+        let mut res = GQLResponse::from_nodes(vec![
+            // A past occurrence is replaced by the next upcoming one
+            Node {
+                series: Some(Series {
+                    events: Events {
+                        edges: vec![
+                            occurrence("1", "2000-12-01T18:00:00-05:00"),
+                            occurrence("3", "2099-12-15T18:00:00-05:00"),
+                            occurrence("2", "2099-12-08T18:00:00-05:00"),
+                        ],
+                    },
+                }),
+                ..Node::at("1", "2000-12-01T18:00:00-05:00")
+            },
+            // Another occurrence of the same series
+            Node::at("3", "2099-12-15T18:00:00-05:00"),
+            Node::at("4", "2099-12-02T18:00:00-05:00"),
+        ]);
+
+        res.apply_series_mode(&SeriesMode::Collapse);
+        let edges = &res.data.unwrap().result.edges;
+        let ids: Vec<&str> = edges.iter().map(|e| e.node.id.as_str()).collect();
+        assert_eq!(ids, ["2", "4"]);
+        assert!(edges.iter().all(|e| e.node.series.is_none()));
+        assert_eq!(edges[0].node.date_time, "2099-12-08T18:00:00-05:00");
+        assert_eq!(
+            edges[0].node.event_url,
+            "https://www.meetup.com/rust-toronto/events/2/"
+        );
+    }
+
+    #[test]
+    /// Results of several search queries are merged, listing every query each event matched, and
+    /// events matching the most queries are sorted first
+    fn can_merge_search_results() {
+        let event = |id: &str| Node::at(id, "2024-12-01T18:00:00-05:00");
+        let mut rust = GQLResponse::from_nodes(vec![event("1"), event("2")]);
+        rust.tag_matched_query("rust");
+        let mut toronto = GQLResponse::from_nodes(vec![event("3"), event("2")]);
+        toronto.tag_matched_query("toronto");
+
+        let mut res = GQLResponse::default();
+        res.merge(rust);
+        res.merge(GQLResponse::default());
+        res.merge(toronto);
+        assert_eq!(res.data.as_ref().unwrap().result.total_count, 4);
+        assert_eq!(res.data.as_ref().unwrap().result.edges.len(), 4);
+
+        res.dedup(&DedupMode::Id);
+        res.sort_by_matched_queries();
+        let edges = &res.data.unwrap().result.edges;
+        let matches: Vec<(&str, Vec<String>)> = edges
+            .iter()
+            .map(|e| {
+                (
+                    e.node.id.as_str(),
+                    e.node.matched_queries.clone().unwrap_or_default(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            matches,
+            [
+                ("2", vec!["rust".to_string(), "toronto".to_string()]),
+                ("1", vec!["rust".to_string()]),
+                ("3", vec!["toronto".to_string()]),
+            ]
+        );
+    }
+
+    #[test]
+    /// Events cross posted by different groups are merged into one
+    fn can_dedup_cross_posted_events() {
+        let event = |id: &str, title: &str, urlname: &str| Edge {
+            node: Node {
+                id: id.into(),
+                title: title.into(),
+                date_time: "2024-12-01T18:00:00-05:00".into(),
+                group: Group {
+                    id: urlname.into(),
+                    urlname: urlname.into(),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut res = GQLResponse {
+            data: Some(GQLData {
+                result: MeetupResult {
+                    edges: vec![
+                        event("1", "Rust Toronto: December Meetup", "rust-toronto"),
+                        event("1", "Rust Toronto: December Meetup", "rust-toronto"),
+                        event("2", "Rust Toronto - December meetup!", "toronto-devs"),
+                        event("3", "Board games night", "toronto-devs"),
+                        // Similar events by the same group are different events
+                        event("4", "Rust Toronto - December meetup!", "rust-toronto"),
+                    ],
+                    ..Default::default()
+                },
+            }),
+            errors: None,
+        };
+
+        let mut by_id = res.clone();
+        by_id.dedup(&DedupMode::Id);
+        assert_eq!(by_id.data.unwrap().result.edges.len(), 4);
+
+        res.dedup(&DedupMode::Fuzzy);
+        let edges = &res.data.unwrap().result.edges;
+        assert_eq!(edges.len(), 3);
+        let groups = edges[0].node.cross_posted_groups.as_ref().unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].urlname, "toronto-devs");
+    }
 }
@@ -7,7 +7,7 @@ use serde_json::Value;
 use utoipa::ToSchema;
 
 use crate::{
-    meetup::query::common::{EventType, OperationName2},
+    meetup::query::common::{EventType, IndexAlias, OperationName2, SortField},
     utils::now,
 };
 
@@ -16,7 +16,7 @@ use super::{gql2::GQLResponse, post};
 /// Represents the body of an API request to the Meetup graphql API
 #[derive(Serialize, Debug)]
 #[serde(rename_all = "camelCase")]
-struct SearchRequest<T>
+pub struct SearchRequest<T>
 where
     T: Serialize + for<'de> Deserialize<'de>,
 {
@@ -60,8 +60,7 @@ impl RecommendedEventsWithSeries {
 }
 
 /// Build request for getting RSVP events
-#[allow(dead_code)]
-struct RsvpEvents {}
+pub struct RsvpEvents {}
 
 #[bon]
 impl RsvpEvents {
@@ -75,6 +74,8 @@ impl RsvpEvents {
         start_date: Option<DateTime<Utc>>,
         /// Number of results to return. Defaults to 10
         first: Option<i32>,
+        /// The after cursor
+        after: Option<String>,
     ) -> SearchRequest<RsvpVariables> {
         return SearchRequest::<RsvpVariables> {
             operation_name: OperationName2::getMyRsvps.to_string(),
@@ -88,6 +89,7 @@ impl RsvpEvents {
             variables: RsvpVariables {
                 start_date: Self::format_date(start_date.unwrap_or(Utc::now())),
                 first: first.unwrap_or(10),
+                after,
                 ..Default::default()
             },
         };
@@ -117,7 +119,6 @@ impl<RsvpVariables> SearchRequest<RsvpVariables>
 where
     RsvpVariables: Serialize + for<'de> Deserialize<'de> + std::fmt::Debug,
 {
-    #[allow(dead_code)]
     pub async fn rsvp(&self) -> Result<RsvpResponse> {
         let response = post::<Self, RsvpResponse>(self).await?;
         return Ok(response);
@@ -126,7 +127,7 @@ where
 
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
-struct RsvpVariables {
+pub struct RsvpVariables {
     pub start_date: String,
     pub after: Option<String>,
     pub first: i32,
@@ -142,7 +143,8 @@ impl Default for RsvpVariables {
             after: None,
             first: 10,
             event_status: vec!["UPCOMING".to_string()],
-            rsvp_status: vec!["YES".to_string(), "WAITLIST".to_string()],
+            // Waitlisted events are not returned, the member is not going to them yet
+            rsvp_status: vec!["YES".to_string()],
             extensions: Extensions {
                 persisted_query: PersistedQuery {
                     sha256_hash: "76b2a1649b097ad05cecfff87cc3b038db1f69275129d6e8ad43bc9adbce67f8"
@@ -161,14 +163,14 @@ struct SearchVariables {
     pub first: i32,
     pub lat: f64,
     pub lon: f64,
-    pub sort_field: String,
+    pub sort_field: SortField,
     pub start_date_range: String,
     pub end_date_range: Option<String>,
     /// The after cursor
     pub after: Option<String>,
     /// Type of event
-    pub event_type: String,
-    pub index_alias: String,
+    pub event_type: EventType,
+    pub index_alias: IndexAlias,
     pub do_consolidate_events: bool,
     pub do_promote_paypal_events: bool,
     pub city: String,
@@ -184,12 +186,12 @@ impl Default for SearchVariables {
             lat: 43.7400016784668,
             lon: -79.36000061035156,
             city: "Toronto".into(),
-            sort_field: "RELEVANCE".into(),
+            sort_field: SortField::default(),
             start_date_range: now(),
             end_date_range: None,
             after: None,
-            event_type: EventType::default().to_string(),
-            index_alias: "popular_events_nearby_current".into(),
+            event_type: EventType::default(),
+            index_alias: IndexAlias::default(),
             do_consolidate_events: true,
             do_promote_paypal_events: false,
             number_of_events_for_series: 5,
@@ -200,13 +202,13 @@ impl Default for SearchVariables {
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-struct Extensions {
+pub struct Extensions {
     pub persisted_query: PersistedQuery,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-struct PersistedQuery {
+pub struct PersistedQuery {
     pub sha256_hash: String,
     pub version: i32,
 }
@@ -214,7 +216,7 @@ struct PersistedQuery {
 /// Response from getting RSVPed events
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
-struct RsvpResponse {
+pub struct RsvpResponse {
     pub data: RsvpData,
 }
 
@@ -240,21 +242,21 @@ struct RsvpResponse {
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
-struct RsvpData {
+pub struct RsvpData {
     #[serde(rename = "self")]
     pub self_field: SelfField,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
-struct SelfField {
+pub struct SelfField {
     pub id: String,
     pub upcoming_events: UpcomingEvents,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
-struct UpcomingEvents {
+pub struct UpcomingEvents {
     pub total_count: i64,
     pub page_info: PageInfo,
     pub edges: Option<Vec<Edge>>,
@@ -262,21 +264,21 @@ struct UpcomingEvents {
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
-struct PageInfo {
+pub struct PageInfo {
     pub has_next_page: bool,
     pub end_cursor: Option<String>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
-struct Edge {
+pub struct Edge {
     pub cursor: String,
     pub node: Node,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
-struct Node {
+pub struct Node {
     pub id: String,
     pub rsvp_state: String,
     pub event: Event,
@@ -284,7 +286,7 @@ struct Node {
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
-struct Event {
+pub struct Event {
     pub id: String,
     pub title: String,
     pub date_time: String,
@@ -292,7 +294,7 @@ struct Event {
     pub end_time: String,
     pub going: Going,
     pub featured_event_photo: FeaturedEventPhoto,
-    pub event_type: String,
+    pub event_type: EventType,
     pub group: Group,
     pub is_saved: bool,
     pub hosts: Vec<Host>,
@@ -305,20 +307,20 @@ struct Event {
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
-struct Going {
+pub struct Going {
     pub total_count: i64,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
-struct FeaturedEventPhoto {
+pub struct FeaturedEventPhoto {
     pub id: String,
     pub source: String,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
-struct Group {
+pub struct Group {
     pub id: String,
     pub name: String,
     pub urlname: String,
@@ -334,21 +336,21 @@ struct Group {
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
-struct KeyGroupPhoto {
+pub struct KeyGroupPhoto {
     pub id: String,
     pub source: String,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
-struct Host {
+pub struct Host {
     pub id: String,
     pub name: String,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
-struct Venue {
+pub struct Venue {
     pub name: String,
     pub address: String,
     pub city: String,
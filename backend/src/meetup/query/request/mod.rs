@@ -4,8 +4,11 @@ use reqwest::header::{HeaderMap, HeaderValue};
 use serde::{de::DeserializeOwned, Serialize};
 use tracing::{debug, error};
 
+pub mod category;
+pub mod event;
 pub mod gql2;
 pub mod gql2_v2;
+pub mod venue;
 
 /// Send a post request to the Meetup API
 ///
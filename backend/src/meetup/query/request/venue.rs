@@ -0,0 +1,187 @@
+//! Street addresses of event venues.
+//!
+//! The persisted search queries don't return the address of a venue, so addresses are looked up
+//! with a separate query for the events in a response
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::task::JoinSet;
+use tracing::{debug, error};
+
+use super::event::is_event_id;
+use super::gql2::GQLResponse;
+use super::post;
+
+/// Maximum number of events looked up in a single request
+const MAX_EVENTS_PER_REQUEST: usize = 50;
+
+/// Request for the venue addresses of a list of events
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct VenueAddressRequest {
+    operation_name: String,
+    query: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct VenueAddressResponse {
+    /// Events by alias
+    data: Option<HashMap<String, Option<AddressEvent>>>,
+    errors: Option<Vec<Value>>,
+}
+
+#[derive(Deserialize, Debug)]
+struct AddressEvent {
+    id: String,
+    venue: Option<VenueAddress>,
+}
+
+/// Street address of a venue
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct VenueAddress {
+    pub address: Option<String>,
+    pub postal_code: Option<String>,
+}
+
+impl VenueAddressRequest {
+    /// Build a request for the venue addresses of the events `event_ids`. IDs that are not
+    /// alphanumeric are skipped
+    pub fn new(event_ids: &[&str]) -> Self {
+        let events = event_ids
+            .iter()
+            .filter(|id| is_event_id(id))
+            .enumerate()
+            .map(|(i, id)| {
+                format!("e{i}: event(id: \"{id}\") {{ id venue {{ address postalCode }} }}")
+            })
+            .collect::<Vec<String>>()
+            .join(" ");
+        return Self {
+            operation_name: "venueAddresses".to_string(),
+            query: format!("query venueAddresses {{ {events} }}"),
+        };
+    }
+
+    /// Send the API request, returning the venue address of each event by event ID
+    pub async fn fetch(&self) -> Result<HashMap<String, VenueAddress>> {
+        let response = post::<Self, VenueAddressResponse>(self).await?;
+        let Some(data) = response.data else {
+            return Err(anyhow!(
+                serde_json::to_string(&response.errors).unwrap_or_default()
+            ));
+        };
+        return Ok(data
+            .into_values()
+            .flatten()
+            .filter_map(|event| Some((event.id, event.venue?)))
+            .collect());
+    }
+}
+
+/// Look up the street address of the venue of every in person event in `res`, and format it.
+/// Events keep their venue without an address if the lookup fails
+pub async fn add_venue_addresses(res: &mut GQLResponse) {
+    let Some(data) = res.data.as_ref() else {
+        return;
+    };
+    let event_ids: Vec<&str> = data
+        .result
+        .edges
+        .iter()
+        .filter(|e| e.node.venue.is_some() && !e.node.is_online)
+        .map(|e| e.node.id.as_str())
+        .collect();
+
+    let mut requests = JoinSet::new();
+    for chunk in event_ids.chunks(MAX_EVENTS_PER_REQUEST) {
+        let request = VenueAddressRequest::new(chunk);
+        requests.spawn(async move { request.fetch().await });
+    }
+    let mut addresses = HashMap::new();
+    while let Some(result) = requests.join_next().await {
+        match result {
+            Ok(Ok(chunk_addresses)) => addresses.extend(chunk_addresses),
+            Ok(Err(e)) => error!("Failed to fetch venue addresses: {}", e),
+            Err(e) => error!("Error: {}", e),
+        }
+    }
+    debug!("Found addresses of {} venues", addresses.len());
+    apply_venue_addresses(res, addresses);
+}
+
+/// Set the street address of every venue in `res` from `addresses`, by event ID, and format it
+fn apply_venue_addresses(res: &mut GQLResponse, mut addresses: HashMap<String, VenueAddress>) {
+    let Some(data) = res.data.as_mut() else {
+        return;
+    };
+    for edge in data.result.edges.iter_mut() {
+        let Some(venue) = edge.node.venue.as_mut() else {
+            continue;
+        };
+        if let Some(address) = addresses.remove(&edge.node.id) {
+            venue.address = address.address.filter(|a| !a.trim().is_empty());
+            venue.postal_code = address.postal_code.filter(|p| !p.trim().is_empty());
+        }
+        venue.format_address();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::meetup::query::request::gql2::{Node, Venue};
+
+    #[test]
+    /// Events are looked up by alias, skipping IDs that would break the query
+    fn can_build_request() {
+        let request = VenueAddressRequest::new(&["304823122", "\"} evil", "qxbrstygcqbnb"]);
+        assert_eq!(
+            request.query,
+            "query venueAddresses { e0: event(id: \"304823122\") { id venue { address postalCode } } e1: event(id: \"qxbrstygcqbnb\") { id venue { address postalCode } } }"
+        );
+    }
+
+    #[test]
+    /// Addresses are set on the venue of their event and formatted, ignoring blank addresses
+    fn can_apply_venue_addresses() {
+        let event = |id: &str| Node {
+            venue: Some(Venue {
+                city: "Toronto".into(),
+                state: "ON".into(),
+                country: "ca".into(),
+                ..Default::default()
+            }),
+            ..Node::at(id, "2024-12-01T18:00:00-05:00")
+        };
+        let mut res = GQLResponse::from_nodes(vec![event("1"), event("2"), event("3")]);
+        let address = |address: &str, postal_code: &str| VenueAddress {
+            address: Some(address.into()),
+            postal_code: Some(postal_code.into()),
+        };
+        let addresses = HashMap::from([
+            ("1".to_string(), address("65 Front St W", "M5J 1E6")),
+            ("2".to_string(), address(" ", "")),
+        ]);
+
+        apply_venue_addresses(&mut res, addresses);
+        let venues: Vec<Venue> = res
+            .data
+            .unwrap()
+            .result
+            .edges
+            .into_iter()
+            .map(|e| e.node.venue.unwrap())
+            .collect();
+        assert_eq!(
+            venues[0].formatted_address.as_deref(),
+            Some("65 Front St W, Toronto, ON M5J 1E6")
+        );
+        assert_eq!(venues[1].address, None);
+        assert_eq!(venues[1].postal_code, None);
+        assert_eq!(venues[1].formatted_address, None);
+        assert_eq!(venues[2].formatted_address, None);
+    }
+}
@@ -0,0 +1,346 @@
+//! Background job notifying webhooks of new events found by saved searches
+
+use crate::date_range::parse_date;
+use crate::meetup::query::request::gql2::Edge;
+use crate::routes::meetup::search;
+use crate::saved_search::{is_private_ip, SavedSearch, SavedSearchStore, Webhook};
+use anyhow::{anyhow, Result};
+use hmac::{Hmac, Mac};
+use reqwest::{redirect::Policy, Url};
+use serde::Serialize;
+use sha2::Sha256;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+/// Number of times a webhook request is attempted before giving up
+const MAX_ATTEMPTS: u32 = 4;
+
+/// Maximum number of events listed in the message text
+const MAX_EVENTS_IN_TEXT: usize = 10;
+
+/// Maximum number of characters in the message text. Discord rejects longer messages
+const MAX_TEXT_LENGTH: usize = 2000;
+
+/// Header containing the HMAC-SHA256 signature of the payload
+const SIGNATURE_HEADER: &str = "X-FreshMeet-Signature";
+
+/// Payload posted to webhooks
+///
+/// `text` is read by Slack, and `content` by Discord
+#[derive(Serialize, Debug)]
+struct WebhookPayload {
+    text: String,
+    content: String,
+    saved_search_id: u64,
+    saved_search_name: String,
+    events: Vec<WebhookEvent>,
+}
+
+/// An event in a webhook payload
+#[derive(Serialize, Debug)]
+struct WebhookEvent {
+    id: String,
+    title: String,
+    /// Start time in RFC 3339
+    date_time: String,
+    group: String,
+    event_url: String,
+}
+
+impl From<&Edge> for WebhookEvent {
+    fn from(edge: &Edge) -> Self {
+        return Self {
+            id: edge.node.id.clone(),
+            title: edge.node.title.clone(),
+            date_time: edge.node.date_time.clone(),
+            group: edge.node.group.name.clone(),
+            event_url: edge.node.event_url.clone(),
+        };
+    }
+}
+
+impl WebhookPayload {
+    fn new(saved_search: &SavedSearch, events: Vec<WebhookEvent>) -> Self {
+        let mut text = format!(
+            "{count} new event(s) for \"{name}\":",
+            count = events.len(),
+            name = saved_search.name
+        );
+        for event in events.iter().take(MAX_EVENTS_IN_TEXT) {
+            text.push_str(&format!(
+                "\n• {title} ({date_time}, {group}) {url}",
+                title = event.title,
+                date_time = display_date(&event.date_time),
+                group = event.group,
+                url = event.event_url
+            ));
+        }
+        if events.len() > MAX_EVENTS_IN_TEXT {
+            text.push_str(&format!(
+                "\n…and {} more",
+                events.len() - MAX_EVENTS_IN_TEXT
+            ));
+        }
+        if text.chars().count() > MAX_TEXT_LENGTH {
+            text = text.chars().take(MAX_TEXT_LENGTH - 1).collect();
+            text.push('…');
+        }
+        return Self {
+            content: text.clone(),
+            text,
+            saved_search_id: saved_search.id,
+            saved_search_name: saved_search.name.clone(),
+            events,
+        };
+    }
+}
+
+/// Start time of an event as it is shown in message text, ie `Sun 12-01 06:00pm`, in the offset
+/// of the event
+fn display_date(date_time: &str) -> String {
+    return match parse_date(date_time) {
+        Some(date) => date.format("%a %m-%d %I:%M%P").to_string(),
+        None => date_time.to_string(),
+    };
+}
+
+/// Re-run every saved search with a webhook every `interval`, and post newly found events to its
+/// webhook
+pub async fn run(store: Arc<SavedSearchStore>, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        for saved_search in store.with_webhooks().await {
+            if let Err(err) = notify(&store, &saved_search).await {
+                error!(
+                    "Failed to notify webhook of saved search {}: {}",
+                    saved_search.id, err
+                );
+            }
+        }
+    }
+}
+
+/// Run `saved_search`, and post events that have not been found before to its webhook.
+///
+/// The first time a saved search is run, found events are remembered without notifying the webhook
+async fn notify(store: &SavedSearchStore, saved_search: &SavedSearch) -> Result<()> {
+    let Some(webhook) = &saved_search.webhook else {
+        return Ok(());
+    };
+    saved_search.validate().map_err(|err| anyhow!(err))?;
+
+    let res = search(saved_search.search.clone()).await?;
+    let edges = res.data.map(|d| d.result.edges).unwrap_or_default();
+    let event_ids: Vec<String> = edges.iter().map(|e| e.node.id.clone()).collect();
+
+    let Some(unseen) = store.unseen_events(saved_search.id, &event_ids).await else {
+        info!(
+            "First run of saved search {}, remembering {} events",
+            saved_search.id,
+            event_ids.len()
+        );
+        return store.mark_seen(saved_search.id, event_ids).await;
+    };
+    if unseen.is_empty() {
+        return Ok(());
+    }
+
+    let events = edges
+        .iter()
+        .filter(|e| unseen.contains(&e.node.id))
+        .map(WebhookEvent::from)
+        .collect();
+    let payload = WebhookPayload::new(saved_search, events);
+    post(webhook, &payload).await?;
+    info!(
+        "Notified webhook of {} new events for saved search {}",
+        unseen.len(),
+        saved_search.id
+    );
+    return store.mark_seen(saved_search.id, unseen).await;
+}
+
+/// Post `payload` to `webhook`, retrying with exponential backoff on failure. The webhook is
+/// checked again before every attempt, and redirects are not followed, so the request can't be
+/// sent into a private network
+async fn post(webhook: &Webhook, payload: &WebhookPayload) -> Result<()> {
+    let body = serde_json::to_vec(payload)?;
+    let mut delay = Duration::from_secs(1);
+    for attempt in 1..=MAX_ATTEMPTS {
+        let mut request = webhook_client(webhook)
+            .await?
+            .post(&webhook.url)
+            .header("content-type", "application/json")
+            .timeout(Duration::from_secs(10))
+            .body(body.clone());
+        if let Some(secret) = &webhook.secret {
+            request = request.header(SIGNATURE_HEADER, sign(secret, &body));
+        }
+
+        let err = match request.send().await {
+            Ok(res) if res.status().is_success() => return Ok(()),
+            Ok(res) if res.status().is_redirection() => {
+                return Err(anyhow!(
+                    "Webhook redirected, which is not followed: {}",
+                    res.status()
+                ));
+            }
+            // Retrying won't help with errors caused by the request itself
+            Ok(res) if res.status().is_client_error() && res.status().as_u16() != 429 => {
+                return Err(anyhow!("Webhook rejected payload: {}", res.status()));
+            }
+            Ok(res) => anyhow!("Webhook responded with {}", res.status()),
+            Err(e) => anyhow!("Failed to make request: {}", e),
+        };
+        if attempt == MAX_ATTEMPTS {
+            return Err(err);
+        }
+        warn!("Attempt {attempt} to post to webhook failed, retrying in {delay:?}: {err}");
+        tokio::time::sleep(delay).await;
+        delay *= 2;
+    }
+    unreachable!("Webhook request is attempted at least once");
+}
+
+/// Client for one request to `webhook`. The host of the webhook is resolved here, and the client
+/// only connects to those addresses, so the host can't resolve to a private address after it
+/// was checked
+async fn webhook_client(webhook: &Webhook) -> Result<reqwest::Client> {
+    webhook.validate().map_err(|err| anyhow!(err))?;
+    let url = Url::parse(&webhook.url)?;
+    let mut builder = reqwest::Client::builder().redirect(Policy::none());
+    let host = url.host_str().unwrap_or_default();
+    // IP addresses are not resolved, and were checked with the webhook
+    if host
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse::<IpAddr>()
+        .is_err()
+    {
+        let port = url.port_or_known_default().unwrap_or(443);
+        let addrs = tokio::net::lookup_host((host, port)).await?.collect();
+        builder = builder.resolve_to_addrs(host, &public_addrs(host, addrs)?);
+    }
+    return Ok(builder.build()?);
+}
+
+/// The addresses `host` resolved to, if they are all public
+fn public_addrs(host: &str, addrs: Vec<SocketAddr>) -> Result<Vec<SocketAddr>> {
+    if addrs.is_empty() {
+        return Err(anyhow!(
+            "Webhook host `{host}` did not resolve to any address"
+        ));
+    }
+    if let Some(addr) = addrs.iter().find(|addr| is_private_ip(addr.ip())) {
+        return Err(anyhow!(
+            "Webhook host `{host}` resolves to the private address {}",
+            addr.ip()
+        ));
+    }
+    return Ok(addrs);
+}
+
+/// Sign `body` with HMAC-SHA256 using `secret`. Formatted as `sha256=<hex digest>`
+fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(body);
+    return format!("sha256={}", hex::encode(mac.finalize().into_bytes()));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn saved_search() -> SavedSearch {
+        return serde_json::from_value(serde_json::json!({
+            "id": 1,
+            "user": "alice",
+            "name": "Rust meetups",
+            "webhook": null,
+            "query": "rust",
+        }))
+        .unwrap();
+    }
+
+    #[test]
+    /// Payloads are signed with HMAC-SHA256
+    fn can_sign_payload() {
+        assert_eq!(
+            sign("key", b"The quick brown fox jumps over the lazy dog"),
+            "sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+    }
+
+    #[test]
+    /// Webhooks are only sent to hosts resolving to public addresses
+    fn can_reject_private_addrs() {
+        let addr = |addr: &str| -> SocketAddr { addr.parse().unwrap() };
+        assert!(public_addrs("hooks.slack.com", vec![addr("54.230.0.1:443")]).is_ok());
+        assert!(public_addrs("hooks.slack.com", vec![]).is_err());
+        for private in ["127.0.0.1:443", "169.254.169.254:443", "[::1]:443"] {
+            assert!(
+                public_addrs(
+                    "evil.example.com",
+                    vec![addr("54.230.0.1:443"), addr(private)]
+                )
+                .is_err(),
+                "Resolving to {private}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    /// Webhooks are checked again before they are posted to
+    async fn can_check_webhook_before_posting() {
+        let webhook = |url: &str| Webhook {
+            url: url.into(),
+            secret: None,
+        };
+        assert!(webhook_client(&webhook("https://localhost/hook"))
+            .await
+            .is_err());
+        assert!(webhook_client(&webhook("https://[::1]/hook"))
+            .await
+            .is_err());
+        assert!(webhook_client(&webhook("https://203.0.113.7/hook"))
+            .await
+            .is_ok());
+    }
+
+    #[test]
+    /// Message text is cut to the length Discord accepts
+    fn can_limit_text_length() {
+        let events = (0..20)
+            .map(|i| WebhookEvent {
+                id: i.to_string(),
+                title: "Rust ".repeat(100),
+                date_time: "2024-12-01T18:00:00-05:00".into(),
+                group: "Rust Toronto".into(),
+                event_url: format!("https://www.meetup.com/rust-toronto/events/{i}/"),
+            })
+            .collect();
+        let payload = WebhookPayload::new(&saved_search(), events);
+        assert_eq!(payload.content.chars().count(), MAX_TEXT_LENGTH);
+        assert!(payload.content.ends_with('…'));
+        assert_eq!(payload.events.len(), 20);
+    }
+
+    #[test]
+    /// Events keep their RFC 3339 start time, which is only formatted for the message text
+    fn can_format_dates_in_text_only() {
+        let event = WebhookEvent {
+            id: "1".into(),
+            title: "Rust Toronto".into(),
+            date_time: "2024-12-01T18:00:00-05:00".into(),
+            group: "Rust Toronto".into(),
+            event_url: "https://www.meetup.com/rust-toronto/events/1/".into(),
+        };
+        let payload = WebhookPayload::new(&saved_search(), vec![event]);
+        assert_eq!(payload.events[0].date_time, "2024-12-01T18:00:00-05:00");
+        assert!(payload.text.contains("(Sun 12-01 06:00pm, Rust Toronto)"));
+    }
+}
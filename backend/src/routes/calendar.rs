@@ -0,0 +1,209 @@
+//! Route handlers for iCalendar feeds
+
+use crate::ical::{render, CalendarEvent};
+use crate::meetup::model::Event;
+use crate::meetup::query::request::event::EventRequest;
+use crate::meetup::query::request::gql2::GQLResponse;
+use crate::meetup::query::request::gql2_v2::{self, RsvpEvents};
+use crate::meetup::query::request::venue::add_venue_addresses;
+use crate::routes::error::ApiError;
+use crate::routes::meetup::{recommended, RecommendedMeetupsQueryParams};
+use crate::routes::AppState;
+use crate::utils::constant_time_eq;
+use anyhow::Result;
+use axum::{
+    extract::{Path, Query, State},
+    http::header,
+    response::IntoResponse,
+};
+use common_axum::axum::AppError;
+use tracing::{error, warn};
+
+/// Number of pages of RSVPs to include in the RSVP calendar feed
+const MAX_RSVP_PAGES: usize = 10;
+
+/// Gets an event as an iCalendar file
+#[utoipa::path(
+    get,
+    path = "/events/{id}.ics",
+    responses(
+        (status = 200, description = "Found event", body = String, content_type = "text/calendar"),
+        (status = 404, description = "Event could not be found", body = String),
+        (status = 500, description = "Failed to fetch event", body = String)
+    ),
+    params(
+        ("id" = String, Path, description = "ID of the event")
+    )
+)]
+pub async fn event_calendar_handler(
+    Path(file): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let Some(id) = file.strip_suffix(".ics") else {
+        return Err(ApiError::NotFound(format!("{file} does not exist")));
+    };
+    let Some(request) = EventRequest::new(id) else {
+        return Err(ApiError::NotFound(format!("Event {id} could not be found")));
+    };
+    let event = match request.fetch().await {
+        Ok(Some(event)) => event,
+        Ok(None) => return Err(ApiError::NotFound(format!("Event {id} could not be found"))),
+        Err(err) => {
+            error!("Failed to look up event {}: {}", id, err);
+            return Err(ApiError::Internal(AppError(err)));
+        }
+    };
+    let calendar_event = CalendarEvent::try_from(&Event::from(&event))
+        .map_err(|err| ApiError::Internal(AppError(err)))?;
+    return Ok(calendar_response(render(&event.title, &[calendar_event])));
+}
+
+/// Gets recommended meetups as an iCalendar feed
+#[utoipa::path(
+    get,
+    path = "/recommended.ics",
+    responses(
+        (status = 200, description = "Found recommended meetups successfully", body = String, content_type = "text/calendar"),
+        (status = 500, description = "Failed to fetch meetups", body = String)
+    ),
+    params(
+        RecommendedMeetupsQueryParams
+    )
+)]
+pub async fn recommended_calendar_handler(
+    query: Query<RecommendedMeetupsQueryParams>,
+) -> Result<impl IntoResponse, AppError> {
+    match recommended(&query).await {
+        Ok(mut res) => {
+            add_venue_addresses(&mut res).await;
+            Ok(calendar_response(render(
+                "FreshMeet recommended",
+                &calendar_events(&res),
+            )))
+        }
+        Err(e) => {
+            error!("Error: {}", e);
+            Err(AppError(e))
+        }
+    }
+}
+
+/// Gets all upcoming events the member is going to as an iCalendar feed.
+///
+/// The feed is only available at the secret token configured with `RSVP_CALENDAR_TOKEN`, so it
+/// can be subscribed to with `webcal://` without logging in. Cancelled RSVPs are removed from the
+/// feed, and calendar apps remove them on their next refresh.
+#[utoipa::path(
+    get,
+    path = "/rsvps/{token}.ics",
+    responses(
+        (status = 200, description = "Found RSVPed events successfully", body = String, content_type = "text/calendar"),
+        (status = 404, description = "Calendar does not exist", body = String),
+        (status = 500, description = "Failed to fetch RSVPed events", body = String)
+    ),
+    params(
+        ("token" = String, Path, description = "Secret token of the calendar feed")
+    )
+)]
+pub async fn rsvp_calendar_handler(
+    State(state): State<AppState>,
+    Path(file): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let authorized = match (&state.rsvp_calendar_token, file.strip_suffix(".ics")) {
+        (Some(expected), Some(token)) => constant_time_eq(expected.as_bytes(), token.as_bytes()),
+        _ => false,
+    };
+    if !authorized {
+        return Err(ApiError::NotFound("Calendar does not exist".to_string()));
+    }
+
+    let rsvps = match fetch_rsvps().await {
+        Ok(rsvps) => rsvps,
+        Err(err) => {
+            error!("Failed to fetch RSVPs: {}", err);
+            return Err(ApiError::Internal(AppError(err)));
+        }
+    };
+    let mut events: Vec<CalendarEvent> = rsvps
+        .iter()
+        .filter_map(|rsvp| match CalendarEvent::try_from(&Event::from(rsvp)) {
+            Ok(event) => Some(event),
+            Err(err) => {
+                warn!("Skipping event {}: {}", rsvp.id, err);
+                None
+            }
+        })
+        .collect();
+
+    let revisions: Vec<(String, String, String)> = events
+        .iter()
+        .map(|e| (e.uid.clone(), e.start.to_rfc3339(), e.end.to_rfc3339()))
+        .collect();
+    let revisions = match state.event_sequences.revisions(&revisions).await {
+        Ok(revisions) => revisions,
+        Err(err) => {
+            error!("Failed to update event sequences: {}", err);
+            return Err(ApiError::Internal(AppError(err)));
+        }
+    };
+    for (event, revision) in events.iter_mut().zip(revisions) {
+        event.sequence = revision.sequence;
+    }
+
+    return Ok(calendar_response(render("FreshMeet RSVPs", &events)));
+}
+
+/// Convert all events in `res` that can be represented in a calendar
+pub fn calendar_events(res: &GQLResponse) -> Vec<CalendarEvent> {
+    let Some(data) = &res.data else {
+        return vec![];
+    };
+    return data
+        .result
+        .edges
+        .iter()
+        .filter_map(
+            |edge| match CalendarEvent::try_from(&Event::from(&edge.node)) {
+                Ok(event) => Some(event),
+                Err(err) => {
+                    warn!("Skipping event {}: {}", edge.node.id, err);
+                    None
+                }
+            },
+        )
+        .collect();
+}
+
+/// Respond with `calendar` as an iCalendar file
+pub fn calendar_response(calendar: String) -> impl IntoResponse {
+    return (
+        [(header::CONTENT_TYPE, "text/calendar; charset=utf-8")],
+        calendar,
+    );
+}
+
+/// Fetch all upcoming events the member RSVPed yes to
+async fn fetch_rsvps() -> Result<Vec<gql2_v2::Event>> {
+    let mut events = vec![];
+    let mut after = None;
+    for _ in 0..MAX_RSVP_PAGES {
+        let res = RsvpEvents::builder()
+            .first(50)
+            .maybe_after(after)
+            .build()
+            .rsvp()
+            .await?;
+        let upcoming_events = res.data.self_field.upcoming_events;
+        events.extend(
+            upcoming_events
+                .edges
+                .unwrap_or_default()
+                .into_iter()
+                .map(|edge| edge.node.event),
+        );
+        if !upcoming_events.page_info.has_next_page {
+            break;
+        }
+        after = upcoming_events.page_info.end_cursor;
+    }
+    return Ok(events);
+}
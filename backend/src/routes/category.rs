@@ -0,0 +1,29 @@
+//! Route handlers for topic categories
+
+use crate::meetup::query::request::category::Category;
+use crate::routes::error::ApiError;
+use crate::routes::AppState;
+use axum::{extract::State, Json};
+use common_axum::axum::AppError;
+use tracing::error;
+
+/// Lists the topic categories events can be filtered by with `category_id`
+#[utoipa::path(
+    get,
+    path = "/categories",
+    responses(
+        (status = 200, description = "Successfully returned categories", body = Vec<Category>),
+        (status = 500, description = "Failed to fetch categories", body = String)
+    )
+)]
+pub async fn categories_handler(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<Category>>, ApiError> {
+    return match state.categories.get().await {
+        Ok(categories) => Ok(Json(categories.to_vec())),
+        Err(err) => {
+            error!("Failed to fetch categories: {}", err);
+            Err(ApiError::Internal(AppError(err)))
+        }
+    };
+}
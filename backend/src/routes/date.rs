@@ -0,0 +1,49 @@
+//! Route handlers for dates
+
+use crate::date_parser::parse_when;
+use crate::date_range::parse_timezone;
+use crate::routes::error::ApiError;
+use axum::{extract::Query, Json};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+/// Query parameters for `/parse-date` route
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ParseDateQueryParams {
+    /// Dates written in English, ie `tomorrow evening` or `next saturday`
+    pub when: String,
+    /// IANA time zone of the caller, ie `America/Vancouver`. Defaults to `America/New_York`
+    pub timezone: Option<String>,
+}
+
+/// Dates events would be searched in
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ParsedDates {
+    /// Start of the dates, in RFC 3339
+    pub start_date_range: String,
+    /// End of the dates, in RFC 3339
+    pub end_date_range: String,
+}
+
+/// Parses dates written in English the way `when` is parsed by `/search`
+#[utoipa::path(
+    get,
+    path = "/parse-date",
+    responses(
+        (status = 200, description = "Successfully parsed dates", body = ParsedDates),
+        (status = 400, description = "Dates could not be understood", body = String)
+    ),
+    params(ParseDateQueryParams)
+)]
+pub async fn parse_date_handler(
+    Query(query): Query<ParseDateQueryParams>,
+) -> Result<Json<ParsedDates>, ApiError> {
+    let tz = parse_timezone(query.timezone.as_deref()).map_err(ApiError::BadRequest)?;
+    let (start, end) =
+        parse_when(&query.when, Utc::now().with_timezone(&tz)).map_err(ApiError::BadRequest)?;
+    return Ok(Json(ParsedDates {
+        start_date_range: start.to_rfc3339(),
+        end_date_range: end.to_rfc3339(),
+    }));
+}
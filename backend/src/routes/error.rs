@@ -0,0 +1,30 @@
+//! Errors returned by route handlers
+
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use common_axum::axum::AppError;
+
+/// An error that is returned to the client with a matching status code
+pub enum ApiError {
+    /// The request is invalid. Responds with 400
+    BadRequest(String),
+    /// The request is missing valid credentials. Responds with 401
+    Unauthorized(String),
+    /// The requested resource does not exist. Responds with 404
+    NotFound(String),
+    /// Something went wrong on our end. Responds with 500
+    Internal(AppError),
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        match self {
+            ApiError::BadRequest(message) => (StatusCode::BAD_REQUEST, message).into_response(),
+            ApiError::Unauthorized(message) => (StatusCode::UNAUTHORIZED, message).into_response(),
+            ApiError::NotFound(message) => (StatusCode::NOT_FOUND, message).into_response(),
+            ApiError::Internal(err) => err.into_response(),
+        }
+    }
+}
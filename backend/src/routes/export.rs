@@ -0,0 +1,128 @@
+//! Streaming CSV and JSON Lines responses
+
+use crate::export::ExportFormat;
+use crate::meetup::query::request::gql2::GQLResponse;
+use crate::routes::meetup::{
+    recommended, search, RecommendedMeetupsQueryParams, SearchRequestBody,
+};
+use anyhow::Result;
+use axum::{
+    body::Body,
+    http::header,
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use std::collections::HashSet;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tracing::error;
+use utoipa::IntoParams;
+
+/// Maximum number of pages fetched when exporting all pages
+const MAX_EXPORT_PAGES: usize = 25;
+
+/// Query parameters for exporting events from the event routes
+#[derive(Debug, Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportQueryParams {
+    /// Export events in this format instead of responding with JSON
+    pub format: Option<ExportFormat>,
+    /// Export every page of results instead of only the first. Events are sorted per page
+    #[serde(alias = "all_pages")]
+    pub all_pages: Option<bool>,
+}
+
+/// Where exported events come from
+pub enum ExportSource {
+    Recommended(RecommendedMeetupsQueryParams),
+    Search(SearchRequestBody),
+}
+
+impl ExportSource {
+    /// Cursor of the page the caller asked to start from
+    fn start_cursor(&self) -> Option<String> {
+        return match self {
+            ExportSource::Recommended(query) => query.after.clone(),
+            ExportSource::Search(body) => body.after.clone(),
+        };
+    }
+
+    /// Fetch the page of events after the cursor `after`
+    async fn fetch_page(&mut self, after: Option<String>) -> Result<GQLResponse> {
+        return match self {
+            ExportSource::Recommended(query) => {
+                query.after = after;
+                recommended(query).await
+            }
+            ExportSource::Search(body) => {
+                body.after = after;
+                search(body.clone()).await
+            }
+        };
+    }
+}
+
+/// Respond with the events from `source` in `format`.
+///
+/// Rows are streamed as each page of events is fetched, so all pages don't need to be kept in
+/// memory. If a later page fails to be fetched the response is cut short.
+pub async fn export_response(
+    mut source: ExportSource,
+    format: ExportFormat,
+    all_pages: bool,
+) -> Result<Response> {
+    // Fetch the first page before responding, so errors get a proper status code
+    let first_page = source.fetch_page(source.start_cursor()).await?;
+
+    let (tx, rx) = mpsc::channel::<Result<String, std::io::Error>>(4);
+    tokio::spawn(async move {
+        if let Some(header) = format.header() {
+            if tx.send(Ok(header)).await.is_err() {
+                return;
+            }
+        }
+        let mut seen: HashSet<String> = HashSet::new();
+        let mut page = first_page;
+        for page_number in 1.. {
+            let result = page.data.unwrap_or_default().result;
+            // The same event can show up on multiple pages
+            let rows = format.render(
+                result
+                    .edges
+                    .iter()
+                    .filter(|e| seen.insert(e.node.id.clone())),
+            );
+            if tx.send(Ok(rows)).await.is_err() {
+                // Client went away
+                return;
+            }
+            if !all_pages || !result.page_info.has_next_page || page_number >= MAX_EXPORT_PAGES {
+                return;
+            }
+            page = match source.fetch_page(result.page_info.end_cursor).await {
+                Ok(page) => page,
+                Err(err) => {
+                    error!(
+                        "Failed to fetch page {} of export: {}",
+                        page_number + 1,
+                        err
+                    );
+                    let _ = tx.send(Err(std::io::Error::other(err.to_string()))).await;
+                    return;
+                }
+            };
+        }
+    });
+
+    return Ok((
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"events.{}\"", format.extension()),
+            ),
+        ],
+        Body::from_stream(ReceiverStream::new(rx)),
+    )
+        .into_response());
+}
@@ -0,0 +1,194 @@
+//! Route handlers for Atom and RSS feeds
+
+use crate::feed::{escape, Feed, FeedEntry, FeedFormat};
+use crate::meetup::query::request::gql2::GQLResponse;
+use crate::routes::error::ApiError;
+use crate::routes::meetup::{
+    recommended, search, RecommendedMeetupsQueryParams, SearchRequestBody,
+};
+use crate::routes::AppState;
+use anyhow::Result;
+use axum::{
+    extract::{Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use common_axum::axum::AppError;
+use tracing::error;
+use urlencoding::encode;
+
+/// Gets recommended meetups as an Atom feed
+#[utoipa::path(
+    get,
+    path = "/recommended.atom",
+    responses(
+        (status = 200, description = "Found recommended meetups successfully", body = String, content_type = "application/atom+xml"),
+        (status = 500, description = "Failed to fetch meetups", body = String)
+    ),
+    params(
+        RecommendedMeetupsQueryParams
+    )
+)]
+pub async fn recommended_atom_handler(
+    State(state): State<AppState>,
+    query: Query<RecommendedMeetupsQueryParams>,
+) -> Result<Response, AppError> {
+    return recommended_feed(&state, &query, FeedFormat::Atom).await;
+}
+
+/// Gets recommended meetups as an RSS feed
+#[utoipa::path(
+    get,
+    path = "/recommended.rss",
+    responses(
+        (status = 200, description = "Found recommended meetups successfully", body = String, content_type = "application/rss+xml"),
+        (status = 500, description = "Failed to fetch meetups", body = String)
+    ),
+    params(
+        RecommendedMeetupsQueryParams
+    )
+)]
+pub async fn recommended_rss_handler(
+    State(state): State<AppState>,
+    query: Query<RecommendedMeetupsQueryParams>,
+) -> Result<Response, AppError> {
+    return recommended_feed(&state, &query, FeedFormat::Rss).await;
+}
+
+/// Searches meetups, returning an Atom feed. Takes the same parameters as `/search`, as query
+/// parameters so feed readers can subscribe to it
+#[utoipa::path(
+    get,
+    path = "/search.atom",
+    responses(
+        (status = 200, description = "Successfully returned searched meetups", body = String, content_type = "application/atom+xml"),
+        (status = 400, description = "Invalid search parameters", body = String),
+        (status = 500, description = "Failed to search for meetups", body = String)
+    ),
+    params(
+        SearchRequestBody
+    )
+)]
+pub async fn search_atom_handler(
+    State(state): State<AppState>,
+    Query(query): Query<SearchRequestBody>,
+) -> Result<Response, ApiError> {
+    return search_feed(&state, query, FeedFormat::Atom).await;
+}
+
+/// Searches meetups, returning an RSS feed. Takes the same parameters as `/search`, as query
+/// parameters so feed readers can subscribe to it
+#[utoipa::path(
+    get,
+    path = "/search.rss",
+    responses(
+        (status = 200, description = "Successfully returned searched meetups", body = String, content_type = "application/rss+xml"),
+        (status = 400, description = "Invalid search parameters", body = String),
+        (status = 500, description = "Failed to search for meetups", body = String)
+    ),
+    params(
+        SearchRequestBody
+    )
+)]
+pub async fn search_rss_handler(
+    State(state): State<AppState>,
+    Query(query): Query<SearchRequestBody>,
+) -> Result<Response, ApiError> {
+    return search_feed(&state, query, FeedFormat::Rss).await;
+}
+
+/// Respond with recommended meetups as a feed in `format`
+pub async fn recommended_feed(
+    state: &AppState,
+    query: &RecommendedMeetupsQueryParams,
+    format: FeedFormat,
+) -> Result<Response, AppError> {
+    let res = match recommended(query).await {
+        Ok(res) => res,
+        Err(e) => {
+            error!("Error: {}", e);
+            return Err(AppError(e));
+        }
+    };
+    let feed = Feed {
+        id: "urn:freshmeet:recommended".to_string(),
+        title: "FreshMeet recommended".to_string(),
+        link: "https://www.meetup.com/find/".to_string(),
+        entries: vec![],
+    };
+    return feed_response(state, feed, res, format)
+        .await
+        .map_err(AppError);
+}
+
+/// Respond with searched meetups as a feed in `format`
+pub async fn search_feed(
+    state: &AppState,
+    body: SearchRequestBody,
+    format: FeedFormat,
+) -> Result<Response, ApiError> {
+    body.validate().map_err(ApiError::BadRequest)?;
+    let query = body.query.clone().unwrap_or_default();
+    let res = match search(body).await {
+        Ok(res) => res,
+        Err(err) => {
+            error!("Error: {}", err);
+            return Err(ApiError::Internal(AppError(err)));
+        }
+    };
+    let feed = Feed {
+        id: format!("urn:freshmeet:search:{}", encode(&query)),
+        title: format!("FreshMeet search: {query}"),
+        link: format!("https://www.meetup.com/find/?keywords={}", encode(&query)),
+        entries: vec![],
+    };
+    return feed_response(state, feed, res, format)
+        .await
+        .map_err(|err| ApiError::Internal(AppError(err)));
+}
+
+/// Add the events in `res` to `feed`, and respond with it rendered in `format`
+async fn feed_response(
+    state: &AppState,
+    mut feed: Feed,
+    res: GQLResponse,
+    format: FeedFormat,
+) -> Result<Response> {
+    let edges = res.data.map(|d| d.result.edges).unwrap_or_default();
+
+    let revisions: Vec<(String, String, String)> = edges
+        .iter()
+        .map(|e| {
+            (
+                e.node.id.clone(),
+                e.node.date_time.clone(),
+                e.node.end_time.clone().unwrap_or_default(),
+            )
+        })
+        .collect();
+    let revisions = state.feed_revisions.revisions(&revisions).await?;
+
+    for (mut edge, revision) in edges.into_iter().zip(revisions) {
+        edge.description_to_html(&state.html_sanitizer);
+        edge.format_start_date();
+        feed.entries.push(FeedEntry {
+            id: format!("urn:freshmeet:event:{}", edge.node.id),
+            content: format!(
+                "<p>{date_time} · {group}</p>{description}",
+                date_time = escape(&edge.node.date_time),
+                group = escape(&edge.node.group.name),
+                description = edge.node.description
+            ),
+            title: edge.node.title,
+            link: edge.node.event_url,
+            author: edge.node.group.name,
+            updated: revision.updated,
+        });
+    }
+
+    return Ok((
+        [(header::CONTENT_TYPE, format.content_type())],
+        feed.render(format),
+    )
+        .into_response());
+}
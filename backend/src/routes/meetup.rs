@@ -1,47 +1,208 @@
 //! Route handlers for meetups
 
-use crate::meetup::query::common::OperationName2;
-use crate::meetup::query::request::gql2::{GQLResponse, SearchRequest, Variables};
-use crate::meetup::response::{Event, PageInfo};
-use crate::utils::now;
-use axum::{extract::Query, Json};
+use crate::date_parser::parse_when;
+use crate::date_range::{parse_date, parse_timezone, DateBounds, DateShortcut};
+use crate::feed::FeedFormat;
+use crate::fields::{FieldTree, FieldsQueryParams};
+use crate::map_link::{MapLinkQueryParams, TravelMode};
+use crate::meetup::query::common::{EventType, OperationName2, SortField};
+use crate::meetup::query::request::gql2::{
+    DedupMode, Edge, GQLResponse, SearchRequest, SeriesMode, Variables,
+};
+use crate::meetup::query::request::venue::add_venue_addresses;
+use crate::routes::error::ApiError;
+use crate::routes::export::{export_response, ExportQueryParams, ExportSource};
+use crate::routes::feed::{recommended_feed, search_feed};
+use crate::routes::response::events_response;
+use crate::routes::AppState;
+use crate::time_filter::TimeFilter;
+use crate::utils::{deserialize_list, haversine_km, now};
+use anyhow::{anyhow, Result};
+use axum::{
+    extract::{Query, State},
+    http::HeaderMap,
+    response::Response as HttpResponse,
+    Json,
+};
 use chrono::{DateTime, Timelike, Utc};
 use chrono_tz::America::New_York;
 use common_axum::axum::AppError;
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::{sync::Semaphore, task::JoinSet};
 use tracing::{debug, error, info};
 use utoipa::{IntoParams, ToSchema};
 
-/// response body for meetup search
-#[derive(Serialize, Deserialize, Debug)]
-pub struct Response {
-    pub page_info: PageInfo,
-    pub nodes: Vec<Event>,
-}
-
 /// Query parameters for `/today` route
-#[derive(Debug, Deserialize, IntoParams)]
+#[derive(Debug, Clone, Deserialize, IntoParams)]
 #[serde(rename_all = "camelCase")]
 pub struct RecommendedMeetupsQueryParams {
     pub start_date: DateTime<Utc>,
     pub end_date: DateTime<Utc>,
+    /// How events that are part of a series are returned. Defaults to `nested`
+    pub series_mode: Option<SeriesMode>,
+    /// Number of upcoming events to request for each series. Defaults to 5
+    pub series_events: Option<i32>,
+    /// How duplicated events are detected. Defaults to `id`
+    pub dedup: Option<DedupMode>,
+    /// The after cursor
+    pub after: Option<String>,
+    /// Latitude to compute the distance to events from
+    pub from_lat: Option<f64>,
+    /// Longitude to compute the distance to events from
+    pub from_lon: Option<f64>,
+    /// Only return in person events at most this many kilometres away. Requires `fromLat` and
+    /// `fromLon`. Online events are always returned
+    pub max_distance_km: Option<f64>,
+    /// Sort events by distance, closest first. Requires `fromLat` and `fromLon`
+    pub sort_by_distance: Option<bool>,
+    /// Comma separated travel modes to estimate travel times to events for, ie `walking,transit`.
+    /// Requires `fromLat` and `fromLon`, and a routing service to be configured
+    pub travel_modes: Option<String>,
+    /// Field Meetup sorts events by, one of `RELEVANCE`, `DATETIME` or `DISTANCE`. Events are
+    /// sorted by start time if not set
+    pub sort_field: Option<SortField>,
+    /// Types of events to return. Defaults to `physical`
+    pub event_type: Option<EventTypeFilter>,
+    /// Only return events in this topic category. Categories are listed by `/categories`
+    pub category_id: Option<String>,
+    /// Comma separated days of the week events start on, ie `sat,sun` or `weekdays`. Days are
+    /// in the time zone of the group hosting the event
+    pub days_of_week: Option<String>,
+    /// Only return events starting at or after this time of day, ie `18:00`. Times are in the
+    /// time zone of the group hosting the event
+    pub time_from: Option<String>,
+    /// Only return events starting at or before this time of day, ie `21:30`. Wraps past
+    /// midnight if earlier than `timeFrom`
+    pub time_to: Option<String>,
+}
+
+impl RecommendedMeetupsQueryParams {
+    /// Check the query parameters are valid, returning a description of the problem if not
+    pub fn validate(&self) -> Result<(), String> {
+        let travel_modes = self.travel_modes()?;
+        validate_series_events(self.series_events, ParamCase::Camel)?;
+        validate_sort_field(self.sort_field.as_ref(), ParamCase::Camel)?;
+        validate_cursor(self.event_type, self.after.as_deref())?;
+        self.time_filter()?;
+        validate_distance(
+            self.from_lat,
+            self.from_lon,
+            self.max_distance_km,
+            self.sort_by_distance,
+            ParamCase::Camel,
+        )?;
+        return validate_travel_modes(self.from_lat, &travel_modes, ParamCase::Camel);
+    }
+
+    /// Days and times events must start at
+    pub fn time_filter(&self) -> Result<TimeFilter, String> {
+        let days_of_week: Vec<&str> = self
+            .days_of_week
+            .as_deref()
+            .map(|days| days.split(',').collect())
+            .unwrap_or_default();
+        return TimeFilter::parse(
+            &days_of_week,
+            self.time_from.as_deref(),
+            self.time_to.as_deref(),
+            ParamCase::Camel,
+        );
+    }
+
+    /// Travel modes to estimate travel times for
+    pub fn travel_modes(&self) -> Result<Vec<TravelMode>, String> {
+        let Some(travel_modes) = &self.travel_modes else {
+            return Ok(vec![]);
+        };
+        return travel_modes
+            .split(',')
+            .map(|mode| mode.trim().parse())
+            .collect();
+    }
 }
 
-/// Gets recommended meetups
+/// Gets recommended meetups. Responds with a feed if an Atom or RSS feed is requested in the
+/// `Accept` header
 #[utoipa::path(
     get,
     path = "/recommended",
     responses(
-        (status = 200, description = "Found recommended meetups successfully", body = GQLResponse),
+        (status = 200, description = "Found recommended meetups successfully", content(
+            (GQLResponse = "application/json"),
+            (String = "application/atom+xml"),
+            (String = "application/rss+xml"),
+            (String = "text/csv"),
+            (String = "application/jsonl")
+        )),
+        (status = 400, description = "Invalid query parameters", body = String),
         (status = 500, description = "Failed to fetch meetups", body = String)
     ),
     params(
-        RecommendedMeetupsQueryParams
+        RecommendedMeetupsQueryParams,
+        ExportQueryParams,
+        MapLinkQueryParams,
+        FieldsQueryParams
     )
 )]
 pub async fn recommended_meetups_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
     query: Query<RecommendedMeetupsQueryParams>,
-) -> Result<Json<GQLResponse>, AppError> {
+    Query(export): Query<ExportQueryParams>,
+    Query(map_link): Query<MapLinkQueryParams>,
+    Query(fields): Query<FieldsQueryParams>,
+) -> Result<HttpResponse, ApiError> {
+    query.validate().map_err(ApiError::BadRequest)?;
+    let field_tree = fields.field_tree().map_err(ApiError::BadRequest)?;
+    let description_fields = fields.description_fields().map_err(ApiError::BadRequest)?;
+    if let Some(format) = FeedFormat::from_accept(&headers) {
+        return recommended_feed(&state, &query, format)
+            .await
+            .map_err(ApiError::Internal);
+    }
+    if let Some(format) = export.format {
+        let source = ExportSource::Recommended(query.0);
+        return match export_response(source, format, export.all_pages.unwrap_or(false)).await {
+            Ok(res) => Ok(res),
+            Err(e) => {
+                error!("Error: {}", e);
+                Err(ApiError::Internal(AppError(e)))
+            }
+        };
+    }
+    match recommended(&query).await {
+        Ok(mut res) => {
+            if needs_venue_addresses(field_tree.as_ref(), &map_link, &state) {
+                add_venue_addresses(&mut res).await;
+            }
+            let travel_modes = query.travel_modes().unwrap_or_default();
+            let from = query.from_lat.zip(query.from_lon);
+            estimate_travel_times(&state, &mut res, from, &travel_modes).await;
+            map_link.generate_map_urls(&mut res, state.map_provider);
+            res.add_description_fields(&description_fields);
+
+            if let Some(data) = &res.data {
+                debug!("Number of events: {}", data.result.total_count);
+                debug!("After cursor: {:?}", data.result.page_info.end_cursor);
+            }
+
+            Ok(events_response(
+                res,
+                field_tree.as_ref(),
+                &state.html_sanitizer,
+            ))
+        }
+        Err(e) => {
+            error!("Error: {}", e);
+            Err(ApiError::Internal(AppError(e)))
+        }
+    }
+}
+
+/// Get recommended meetups between the dates in `query`. Events are sorted, but not formatted,
+/// and venue addresses are not looked up
+pub async fn recommended(query: &RecommendedMeetupsQueryParams) -> Result<GQLResponse> {
     let start_date_range = query
         .start_date
         .with_timezone(&New_York)
@@ -56,108 +217,811 @@ pub async fn recommended_meetups_handler(
         .expect("Failed to set end time to end of day")
         .to_rfc3339();
 
-    match SearchRequest::builder()
-        .operation_name(OperationName2::recommendedEventsWithSeries)
-        .variables(Variables {
+    let mut res = fetch_events(
+        OperationName2::recommendedEventsWithSeries,
+        Variables {
             first: 200,
             start_date_range,
             end_date_range: Some(end_date_range),
+            number_of_events_for_series: query.series_events.unwrap_or(5),
+            after: query.after.clone(),
+            sort_field: query.sort_field.clone().unwrap_or_default(),
+            category_id: query.category_id.clone(),
             ..Default::default()
-        })
-        .build()
-        .fetch()
-        .await
-    {
-        Ok(mut res) => {
-            // Sort by events starting first
-            debug_assert!(
-                res.data.is_some(),
-                "There should always be data here. Something is wrong if there is no data"
-            );
-            res.sort();
-            res.generate_google_maps_url();
-            res.format();
+        },
+        query.event_type.unwrap_or_default(),
+    )
+    .await?;
+    // Sort by events starting first
+    debug_assert!(
+        res.data.is_some(),
+        "There should always be data here. Something is wrong if there is no data"
+    );
+    res.apply_series_mode(&query.series_mode.clone().unwrap_or_default());
+    res.dedup(&query.dedup.clone().unwrap_or_default());
+    res.filter_by_time(&query.time_filter().map_err(|e| anyhow!(e))?);
+    // Keep the order Meetup sorted events in when a sort field is requested
+    if query.sort_field.is_none() {
+        res.sort();
+    }
+    apply_distance(
+        &mut res,
+        query.from_lat.zip(query.from_lon),
+        query.max_distance_km,
+        query.sort_by_distance.unwrap_or(false),
+    );
+    return Ok(res);
+}
 
-            if let Some(data) = &res.data {
-                debug!("Number of events: {}", data.result.total_count);
-                debug!("After cursor: {:?}", data.result.page_info.end_cursor);
+/// Types of events to return
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum EventTypeFilter {
+    /// In person events
+    #[default]
+    Physical,
+    Online,
+    /// In person events that are also streamed online
+    Hybrid,
+    /// Events of every type
+    Any,
+}
+
+impl EventTypeFilter {
+    /// Event types Meetup is queried for
+    fn event_types(&self) -> Vec<EventType> {
+        return match self {
+            EventTypeFilter::Physical => vec![EventType::Physical],
+            EventTypeFilter::Online => vec![EventType::Online],
+            EventTypeFilter::Hybrid => vec![EventType::Hybrid],
+            EventTypeFilter::Any => vec![EventType::Physical, EventType::Online, EventType::Hybrid],
+        };
+    }
+}
+
+/// Fetch events of the types in `event_type`. When several types are requested Meetup is queried
+/// for each type with a share of `first`, and the events are merged, with the cursors of every
+/// query combined into one
+async fn fetch_events(
+    operation_name: OperationName2,
+    variables: Variables,
+    event_type: EventTypeFilter,
+) -> Result<GQLResponse> {
+    let event_types = event_type.event_types();
+    if let [event_type] = event_types.as_slice() {
+        return SearchRequest::builder()
+            .operation_name(operation_name)
+            .variables(Variables {
+                event_type: event_type.clone(),
+                ..variables
+            })
+            .build()
+            .fetch()
+            .await;
+    }
+
+    // Event types that ran out of events are left out of the cursor, and not queried again
+    let queries: Vec<(EventType, Option<String>)> = match &variables.after {
+        Some(after) => decode_cursor(after)?
+            .into_iter()
+            .map(|(event_type, cursor)| (event_type, Some(cursor)))
+            .collect(),
+        None => event_types.into_iter().map(|t| (t, None)).collect(),
+    };
+    let shares = split_first(variables.first, queries.len());
+    let mut requests = JoinSet::new();
+    for (i, ((event_type, after), first)) in queries.into_iter().zip(shares).enumerate() {
+        let request = SearchRequest::builder()
+            .operation_name(operation_name.clone())
+            .variables(Variables {
+                event_type: event_type.clone(),
+                after,
+                first,
+                ..variables.clone()
+            })
+            .build();
+        requests.spawn(async move { (i, event_type, request.fetch().await) });
+    }
+    let mut results = Vec::with_capacity(requests.len());
+    while let Some(result) = requests.join_next().await {
+        results.push(result?);
+    }
+    results.sort_by_key(|(i, _, _)| *i);
+    let results = results
+        .into_iter()
+        .map(|(_, event_type, res)| Ok((event_type, res?)))
+        .collect::<Result<Vec<_>>>()?;
+    return Ok(merge_event_types(
+        results,
+        &variables.sort_field,
+        (variables.lat, variables.lon),
+    ));
+}
+
+/// Number of events to ask for in each of `queries` queries, so together they return at most
+/// `first` events. Every query asks for at least one event
+fn split_first(first: i32, queries: usize) -> Vec<i32> {
+    let queries = queries.max(1) as i32;
+    return (0..queries)
+        .map(|i| (first / queries + i32::from(i < first % queries)).max(1))
+        .collect();
+}
+
+/// Merge the responses of the query for each event type into one page ordered by `sort_field`.
+/// Meetup ranks each response by relevance on its own, so relevance ranks are interleaved, and
+/// distances are measured from `location`, the location Meetup searched from
+fn merge_event_types(
+    results: Vec<(EventType, GQLResponse)>,
+    sort_field: &SortField,
+    location: (f64, f64),
+) -> GQLResponse {
+    let mut response = GQLResponse {
+        data: Some(Default::default()),
+        ..Default::default()
+    };
+    let mut cursors = vec![];
+    let mut ranks = vec![];
+    for (event_type, res) in results {
+        if let Some(data) = &res.data {
+            let page_info = &data.result.page_info;
+            if let (true, Some(cursor)) = (page_info.has_next_page, &page_info.end_cursor) {
+                cursors.push((event_type, cursor.clone()));
             }
+            ranks.extend(0..data.result.edges.len());
+        }
+        response.merge(res);
+    }
+    let result = &mut response
+        .data
+        .as_mut()
+        .expect("Merged responses always have data")
+        .result;
+    match sort_field {
+        SortField::DateTime => result.edges.sort_by_key(|e| {
+            let start = parse_date(&e.node.date_time);
+            (start.is_none(), start)
+        }),
+        SortField::Distance => result.edges.sort_by(|a, b| {
+            let distance = |e: &Edge| match &e.node.venue {
+                Some(venue) if !e.node.is_online => {
+                    Some(haversine_km(location, (venue.lat, venue.lon)))
+                }
+                _ => None,
+            };
+            let (a, b) = (distance(a), distance(b));
+            return a
+                .is_none()
+                .cmp(&b.is_none())
+                .then_with(|| a.unwrap_or_default().total_cmp(&b.unwrap_or_default()));
+        }),
+        SortField::Relevance | SortField::Other(_) => {
+            let mut ranked: Vec<_> = ranks.into_iter().zip(result.edges.drain(..)).collect();
+            ranked.sort_by_key(|(rank, _)| *rank);
+            result.edges = ranked.into_iter().map(|(_, edge)| edge).collect();
+        }
+    }
+    result.page_info.has_next_page = !cursors.is_empty();
+    result.page_info.end_cursor = (!cursors.is_empty()).then(|| encode_cursor(&cursors));
+    return response;
+}
+
+/// Combine the cursor of the query for each event type into one opaque cursor
+fn encode_cursor(cursors: &[(EventType, String)]) -> String {
+    return hex::encode(serde_json::to_string(cursors).expect("Cursors are always valid JSON"));
+}
+
+/// Split a cursor made by `encode_cursor` into the cursor of the query for each event type
+fn decode_cursor(cursor: &str) -> Result<Vec<(EventType, String)>> {
+    let json = hex::decode(cursor).map_err(|_| anyhow!("Invalid cursor `{cursor}`"))?;
+    return serde_json::from_slice(&json).map_err(|_| anyhow!("Invalid cursor `{cursor}`"));
+}
+
+/// Check `after` is a cursor for `event_type`, returning a description of the problem if not
+fn validate_cursor(event_type: Option<EventTypeFilter>, after: Option<&str>) -> Result<(), String> {
+    if let (Some(EventTypeFilter::Any), Some(after)) = (event_type, after) {
+        decode_cursor(after).map_err(|e| e.to_string())?;
+    }
+    return Ok(());
+}
+
+/// Naming convention of request parameters, so errors name parameters as they are sent.
+/// Recommended event parameters are camel case, and search parameters are snake case, also when
+/// searches are sent in a query string
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum ParamCase {
+    Camel,
+    Snake,
+}
+
+impl ParamCase {
+    /// Name of the snake case parameter `name` in this naming convention
+    pub(crate) fn name(&self, name: &str) -> String {
+        return match self {
+            ParamCase::Snake => name.to_string(),
+            ParamCase::Camel => name
+                .split('_')
+                .enumerate()
+                .map(|(i, word)| {
+                    let mut chars = word.chars();
+                    return match chars.next() {
+                        Some(first) if i > 0 => first.to_uppercase().chain(chars).collect(),
+                        _ => word.to_string(),
+                    };
+                })
+                .collect(),
+        };
+    }
+}
+
+/// Check `series_events` is not negative, returning a description of the problem if it is
+fn validate_series_events(series_events: Option<i32>, case: ParamCase) -> Result<(), String> {
+    if let Some(series_events) = series_events.filter(|n| *n < 0) {
+        return Err(format!(
+            "`{name}` must not be negative, got {series_events}",
+            name = case.name("series_events")
+        ));
+    }
+    return Ok(());
+}
+
+/// Check Meetup can sort by `sort_field`, returning a description of the problem if not
+fn validate_sort_field(sort_field: Option<&SortField>, case: ParamCase) -> Result<(), String> {
+    if let Some(SortField::Other(sort_field)) = sort_field {
+        return Err(format!(
+            "Unknown `{name}` `{sort_field}`",
+            name = case.name("sort_field")
+        ));
+    }
+    return Ok(());
+}
 
-            Ok(Json(res))
+/// Check the distance parameters are valid, returning a description of the problem if not
+fn validate_distance(
+    from_lat: Option<f64>,
+    from_lon: Option<f64>,
+    max_distance_km: Option<f64>,
+    sort_by_distance: Option<bool>,
+    case: ParamCase,
+) -> Result<(), String> {
+    let (from_lat_name, from_lon_name) = (case.name("from_lat"), case.name("from_lon"));
+    if let Some(lat) = from_lat {
+        if !(-90.0..=90.0).contains(&lat) {
+            return Err(format!(
+                "`{from_lat_name}` must be between -90 and 90, got {lat}"
+            ));
         }
-        Err(e) => {
-            error!("Error: {}", e);
-            Err(AppError(e))
+    }
+    if let Some(lon) = from_lon {
+        if !(-180.0..=180.0).contains(&lon) {
+            return Err(format!(
+                "`{from_lon_name}` must be between -180 and 180, got {lon}"
+            ));
+        }
+    }
+    if from_lat.is_some() != from_lon.is_some() {
+        return Err(format!(
+            "`{from_lat_name}` and `{from_lon_name}` must be set together"
+        ));
+    }
+    if from_lat.is_none() && (max_distance_km.is_some() || sort_by_distance == Some(true)) {
+        return Err(format!(
+            "`{from_lat_name}` and `{from_lon_name}` are required to filter or sort by distance"
+        ));
+    }
+    if let Some(max_distance_km) = max_distance_km {
+        if max_distance_km.is_nan() || max_distance_km < 0.0 {
+            return Err(format!(
+                "`{name}` must not be negative, got {max_distance_km}",
+                name = case.name("max_distance_km")
+            ));
         }
     }
+    return Ok(());
+}
+
+/// Check travel times can be estimated, returning a description of the problem if not
+fn validate_travel_modes(
+    from_lat: Option<f64>,
+    travel_modes: &[TravelMode],
+    case: ParamCase,
+) -> Result<(), String> {
+    if from_lat.is_none() && !travel_modes.is_empty() {
+        return Err(format!(
+            "`{from_lat}` and `{from_lon}` are required to estimate travel times",
+            from_lat = case.name("from_lat"),
+            from_lon = case.name("from_lon")
+        ));
+    }
+    return Ok(());
+}
+
+/// Whether venue addresses are returned with events projected to `field_tree`, either in the
+/// venue or in map links from the map app of `map_link`
+pub fn needs_venue_addresses(
+    field_tree: Option<&FieldTree>,
+    map_link: &MapLinkQueryParams,
+    state: &AppState,
+) -> bool {
+    let Some(field_tree) = field_tree else {
+        return true;
+    };
+    let in_venue = ["address", "postalCode", "formattedAddress"]
+        .iter()
+        .any(|field| field_tree.contains(&["venue", field]));
+    let in_map_url =
+        map_link.provider(state.map_provider).uses_address() && field_tree.contains(&["mapUrl"]);
+    return in_venue || in_map_url;
+}
+
+/// Estimate the travel time from `from` to every event by each of `modes`. Does nothing if no
+/// routing service is configured
+pub async fn estimate_travel_times(
+    state: &AppState,
+    res: &mut GQLResponse,
+    from: Option<(f64, f64)>,
+    modes: &[TravelMode],
+) {
+    let (Some(router), Some(from)) = (&state.travel_time_router, from) else {
+        return;
+    };
+    if modes.is_empty() {
+        return;
+    }
+    router.annotate(res, from, modes).await;
+}
+
+/// Add the distance from `from` to every event, then filter and sort events by it
+fn apply_distance(
+    res: &mut GQLResponse,
+    from: Option<(f64, f64)>,
+    max_distance_km: Option<f64>,
+    sort_by_distance: bool,
+) {
+    let Some((lat, lon)) = from else {
+        return;
+    };
+    res.compute_distances(lat, lon);
+    if let Some(max_distance_km) = max_distance_km {
+        res.filter_by_distance(max_distance_km);
+    }
+    if sort_by_distance {
+        res.sort_by_distance();
+    }
 }
 
 /// Body for `/search` route
-#[derive(Serialize, Deserialize, ToSchema)]
+#[derive(Serialize, Deserialize, ToSchema, IntoParams, Debug, Clone)]
 pub struct SearchRequestBody {
     /// Search query
-    query: Option<String>,
-    /// Start date of event
-    start_date: Option<String>,
-    // /// End date of event
-    // end_date: Option<String>,
+    pub query: Option<String>,
+    /// Only return events starting after this date, in RFC 3339. Defaults to now
+    pub start_date: Option<String>,
+    /// Only return events starting before this date, in RFC 3339
+    pub end_date: Option<String>,
+    /// Search in a range of dates relative to now, instead of `start_date` and `end_date`
+    pub date_range: Option<DateShortcut>,
+    /// Dates to search in written in English, ie `tomorrow evening` or `next saturday`, instead
+    /// of `start_date` and `end_date`
+    pub when: Option<String>,
+    /// IANA time zone `date_range` and `when` are resolved in, ie `America/Vancouver`. Defaults
+    /// to `America/New_York`
+    pub timezone: Option<String>,
     /// Events to return per page
-    per_page: Option<u32>,
+    pub per_page: Option<u32>,
+    /// Latitude to search around. Defaults to Toronto
+    pub lat: Option<f64>,
+    /// Longitude to search around. Defaults to Toronto
+    pub lon: Option<f64>,
+    /// City to search in. Defaults to Toronto
+    pub city: Option<String>,
+    /// How events that are part of a series are returned. Defaults to `nested`
+    pub series_mode: Option<SeriesMode>,
+    /// Number of upcoming events to request for each series. Defaults to 5
+    pub series_events: Option<i32>,
+    /// How duplicated events are detected. Defaults to `id`
+    pub dedup: Option<DedupMode>,
+    /// The after cursor
+    pub after: Option<String>,
+    /// Latitude to compute the distance to events from
+    pub from_lat: Option<f64>,
+    /// Longitude to compute the distance to events from
+    pub from_lon: Option<f64>,
+    /// Only return in person events at most this many kilometres away. Requires `from_lat` and
+    /// `from_lon`. Online events are always returned
+    pub max_distance_km: Option<f64>,
+    /// Sort events by distance, closest first. Requires `from_lat` and `from_lon`
+    pub sort_by_distance: Option<bool>,
+    /// Travel modes to estimate travel times to events for, as a list or a comma separated
+    /// string. Requires `from_lat` and `from_lon`, and a routing service to be configured
+    #[serde(default, deserialize_with = "deserialize_list")]
+    pub travel_modes: Option<Vec<TravelMode>>,
+    /// Field Meetup sorts events by, one of `RELEVANCE`, `DATETIME` or `DISTANCE`. Events are
+    /// sorted by start time if not set
+    pub sort_field: Option<SortField>,
+    /// Types of events to return. Defaults to `physical`
+    pub event_type: Option<EventTypeFilter>,
+    /// Only return events in this topic category. Categories are listed by `/categories`
+    pub category_id: Option<String>,
+    /// Days of the week events start on, ie `["sat", "sun"]`, `"sat,sun"` or `["weekdays"]`. Days
+    /// are in the time zone of the group hosting the event
+    #[serde(default, deserialize_with = "deserialize_list")]
+    pub days_of_week: Option<Vec<String>>,
+    /// Only return events starting at or after this time of day, ie `18:00`. Times are in the
+    /// time zone of the group hosting the event
+    pub time_from: Option<String>,
+    /// Only return events starting at or before this time of day, ie `21:30`. Wraps past
+    /// midnight if earlier than `time_from`
+    pub time_to: Option<String>,
+}
+
+impl SearchRequestBody {
+    /// Check the search parameters are valid, returning a description of the problem if not
+    pub fn validate(&self) -> Result<(), String> {
+        self.dates(Utc::now())?;
+        if let Some(per_page) = self.per_page {
+            if !(1..=200).contains(&per_page) {
+                return Err(format!(
+                    "`per_page` must be between 1 and 200, got {per_page}"
+                ));
+            }
+        }
+        if let Some(lat) = self.lat {
+            if !(-90.0..=90.0).contains(&lat) {
+                return Err(format!("`lat` must be between -90 and 90, got {lat}"));
+            }
+        }
+        if let Some(lon) = self.lon {
+            if !(-180.0..=180.0).contains(&lon) {
+                return Err(format!("`lon` must be between -180 and 180, got {lon}"));
+            }
+        }
+        validate_series_events(self.series_events, ParamCase::Snake)?;
+        validate_sort_field(self.sort_field.as_ref(), ParamCase::Snake)?;
+        validate_cursor(self.event_type, self.after.as_deref())?;
+        self.time_filter()?;
+        validate_distance(
+            self.from_lat,
+            self.from_lon,
+            self.max_distance_km,
+            self.sort_by_distance,
+            ParamCase::Snake,
+        )?;
+        return validate_travel_modes(
+            self.from_lat,
+            self.travel_modes.as_deref().unwrap_or_default(),
+            ParamCase::Snake,
+        );
+    }
+
+    /// Days and times events must start at
+    pub fn time_filter(&self) -> Result<TimeFilter, String> {
+        let days_of_week: Vec<&str> = self
+            .days_of_week
+            .iter()
+            .flatten()
+            .map(String::as_str)
+            .collect();
+        return TimeFilter::parse(
+            &days_of_week,
+            self.time_from.as_deref(),
+            self.time_to.as_deref(),
+            ParamCase::Snake,
+        );
+    }
+
+    /// Start and end of the dates to search in, with `date_range` and `when` resolved at `now`.
+    /// Returns a description of the problem if the dates are invalid
+    pub fn dates(&self, now: DateTime<Utc>) -> Result<DateBounds, String> {
+        let tz = parse_timezone(self.timezone.as_deref())?;
+        let relative = match (self.date_range, &self.when) {
+            (Some(_), Some(_)) => {
+                return Err("`date_range` can not be used with `when`".to_string());
+            }
+            (Some(date_range), None) => Some(date_range.resolve(now.with_timezone(&tz))),
+            (None, Some(when)) => Some(parse_when(when, now.with_timezone(&tz))?),
+            (None, None) => None,
+        };
+        if let Some((start, end)) = relative {
+            if self.start_date.is_some() || self.end_date.is_some() {
+                return Err(
+                    "`date_range` and `when` can not be used with `start_date` or `end_date`"
+                        .to_string(),
+                );
+            }
+            return Ok(DateBounds {
+                start: Some(start.fixed_offset()),
+                end: Some(end.fixed_offset()),
+            });
+        }
+
+        let start_date = match &self.start_date {
+            Some(date) => {
+                Some(parse_date(date).ok_or(format!("`start_date` `{date}` is not a valid date"))?)
+            }
+            None => None,
+        };
+        let end_date = match &self.end_date {
+            Some(date) => {
+                Some(parse_date(date).ok_or(format!("`end_date` `{date}` is not a valid date"))?)
+            }
+            None => None,
+        };
+        if let Some(end_date) = end_date {
+            if end_date < start_date.unwrap_or(now.fixed_offset()) {
+                return Err("`end_date` must not be before `start_date`".to_string());
+            }
+        }
+        return Ok(DateBounds {
+            start: start_date,
+            end: end_date,
+        });
+    }
 }
 
-/// Searches meetups. Event end date will not be set, only even start date will be taken into account.
+/// Searches meetups between `start_date` and `end_date`, or in `date_range`.
+/// Responds with a feed if an Atom or RSS feed is requested in the `Accept` header
 #[utoipa::path(
     post,
     path = "/search",
     responses(
-        (status = 200, description = "Successfully returned searched meetups", body = GQLResponse),
+        (status = 200, description = "Successfully returned searched meetups", content(
+            (GQLResponse = "application/json"),
+            (String = "application/atom+xml"),
+            (String = "application/rss+xml"),
+            (String = "text/csv"),
+            (String = "application/jsonl")
+        )),
+        (status = 400, description = "Invalid search parameters", body = String),
         (status = 500, description = "Failed to search for meetups", body = String)
     ),
+    params(
+        ExportQueryParams,
+        MapLinkQueryParams,
+        FieldsQueryParams
+    ),
     request_body = SearchRequestBody
 
 )]
 pub async fn search_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(export): Query<ExportQueryParams>,
+    Query(map_link): Query<MapLinkQueryParams>,
+    Query(fields): Query<FieldsQueryParams>,
     Json(body): Json<SearchRequestBody>,
-) -> Result<Json<GQLResponse>, AppError> {
-    let search_request = SearchRequest::builder()
-        .operation_name(OperationName2::eventSearchWithSeries)
-        .variables(Variables {
-            query: Some(body.query.unwrap_or_default()),
-            start_date_range: body.start_date.unwrap_or(now()),
-            ..Default::default()
-        })
-        .build();
-    debug!("Search request: {:#?}", search_request);
-    // let search_request = SearchRequest::builder()
-    //     .operation_name(OperationName2::eventSearchWithSeries)
-    //     .variables(Variables {
-    //         query: Some(body.query),
-    //         start_date_range: body.start_date.unwrap_or(eod()),
-    //         end_date_range: Some(body.end_date.unwrap_or(now())),
-    //         first: body.per_page.unwrap_or(40) as i32,
-    //         ..Default::default()
-    //     })
-    //     .build();
-
-    info!("Fetching events");
-    let response = match search_request.fetch().await {
+) -> Result<HttpResponse, ApiError> {
+    if let Some(format) = FeedFormat::from_accept(&headers) {
+        return search_feed(&state, body, format).await;
+    }
+    body.validate().map_err(ApiError::BadRequest)?;
+    let field_tree = fields.field_tree().map_err(ApiError::BadRequest)?;
+    let description_fields = fields.description_fields().map_err(ApiError::BadRequest)?;
+    if let Some(format) = export.format {
+        let source = ExportSource::Search(body);
+        return match export_response(source, format, export.all_pages.unwrap_or(false)).await {
+            Ok(res) => Ok(res),
+            Err(err) => {
+                error!("Error: {}", err);
+                Err(ApiError::Internal(AppError(err)))
+            }
+        };
+    }
+    let from = body.from_lat.zip(body.from_lon);
+    let travel_modes = body.travel_modes.clone().unwrap_or_default();
+    match search(body).await {
         Ok(mut res) => {
-            // Sort by events starting first
-            debug_assert!(
-                res.data.is_some(),
-                "There should always be data here. Something is wrong if there is no data"
-            );
-            res.sort();
-            res.format();
-            res
+            if needs_venue_addresses(field_tree.as_ref(), &map_link, &state) {
+                add_venue_addresses(&mut res).await;
+            }
+            estimate_travel_times(&state, &mut res, from, &travel_modes).await;
+            map_link.generate_map_urls(&mut res, state.map_provider);
+            res.add_description_fields(&description_fields);
+            Ok(events_response(
+                res,
+                field_tree.as_ref(),
+                &state.html_sanitizer,
+            ))
         }
         Err(err) => {
             error!("Error: {}", err);
-            return Err(AppError(err));
+            Err(ApiError::Internal(AppError(err)))
         }
+    }
+}
+
+/// Search meetups with the parameters in `body`. Events are sorted, but not formatted, and venue
+/// addresses are not looked up
+pub async fn search(body: SearchRequestBody) -> Result<GQLResponse> {
+    let dates = body.dates(Utc::now()).map_err(|e| anyhow!(e))?;
+    let time_filter = body.time_filter().map_err(|e| anyhow!(e))?;
+    let defaults = Variables::default();
+    let variables = Variables {
+        query: Some(body.query.unwrap_or_default()),
+        start_date_range: dates.start.map_or_else(now, |d| d.to_rfc3339()),
+        end_date_range: dates.end.map(|d| d.to_rfc3339()),
+        first: body.per_page.map_or(defaults.first, |p| p as i32),
+        lat: body.lat.unwrap_or(defaults.lat),
+        lon: body.lon.unwrap_or(defaults.lon),
+        city: body.city.unwrap_or(defaults.city),
+        number_of_events_for_series: body.series_events.unwrap_or(5),
+        after: body.after,
+        sort_field: body.sort_field.clone().unwrap_or_default(),
+        category_id: body.category_id,
+        ..Default::default()
     };
+    info!("Fetching events");
+    let mut res = fetch_events(
+        OperationName2::eventSearchWithSeries,
+        variables,
+        body.event_type.unwrap_or_default(),
+    )
+    .await?;
+    // Sort by events starting first
+    debug_assert!(
+        res.data.is_some(),
+        "There should always be data here. Something is wrong if there is no data"
+    );
+    res.apply_series_mode(&body.series_mode.unwrap_or_default());
+    res.dedup(&body.dedup.unwrap_or_default());
+    res.filter_by_time(&time_filter);
+    // Keep the order Meetup sorted events in when a sort field is requested
+    if body.sort_field.is_none() {
+        res.sort();
+    }
+    apply_distance(
+        &mut res,
+        body.from_lat.zip(body.from_lon),
+        body.max_distance_km,
+        body.sort_by_distance.unwrap_or(false),
+    );
     info!("Events fetched");
-    return Ok(Json(response));
+    return Ok(res);
+}
+
+/// Maximum number of searches sent to Meetup at the same time by `/discover`
+const MAX_CONCURRENT_SEARCHES: usize = 4;
+
+/// Maximum number of search queries in one `/discover` request
+const MAX_DISCOVER_QUERIES: usize = 10;
+
+/// Body for `/discover` route
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct DiscoverRequestBody {
+    /// Search queries to discover events for, at most 10
+    queries: Vec<String>,
+    /// Start date of event, in RFC 3339
+    start_date: Option<String>,
+    /// Events to return per search query, between 1 and 200. Defaults to 40
+    per_query: Option<u32>,
+    /// How duplicated events are detected. Defaults to `id`
+    dedup: Option<DedupMode>,
+}
+
+impl DiscoverRequestBody {
+    /// Check the request is valid, returning a description of the problem if not
+    fn validate(&self) -> Result<(), String> {
+        if self.queries.is_empty() {
+            return Err("At least one search query is required".to_string());
+        }
+        if self.queries.len() > MAX_DISCOVER_QUERIES {
+            return Err(format!(
+                "At most {MAX_DISCOVER_QUERIES} search queries are allowed, got {}",
+                self.queries.len()
+            ));
+        }
+        if let Some(per_query) = self.per_query {
+            if !(1..=200).contains(&per_query) {
+                return Err(format!(
+                    "`per_query` must be between 1 and 200, got {per_query}"
+                ));
+            }
+        }
+        if let Some(start_date) = &self.start_date {
+            if parse_date(start_date).is_none() {
+                return Err(format!(
+                    "`start_date` `{start_date}` is not a valid RFC 3339 date"
+                ));
+            }
+        }
+        return Ok(());
+    }
+}
+
+/// Searches meetups for every query in the request, and merges the results into one response.
+/// Events matching the most queries are returned first.
+#[utoipa::path(
+    post,
+    path = "/discover",
+    responses(
+        (status = 200, description = "Successfully returned discovered meetups", body = GQLResponse),
+        (status = 400, description = "Invalid request", body = String),
+        (status = 500, description = "Failed to search for meetups", body = String)
+    ),
+    params(
+        MapLinkQueryParams,
+        FieldsQueryParams
+    ),
+    request_body = DiscoverRequestBody
+
+)]
+pub async fn discover_handler(
+    State(state): State<AppState>,
+    Query(map_link): Query<MapLinkQueryParams>,
+    Query(fields): Query<FieldsQueryParams>,
+    Json(body): Json<DiscoverRequestBody>,
+) -> Result<HttpResponse, ApiError> {
+    body.validate().map_err(ApiError::BadRequest)?;
+    let field_tree = fields.field_tree().map_err(ApiError::BadRequest)?;
+    let description_fields = fields.description_fields().map_err(ApiError::BadRequest)?;
+
+    let start_date = body
+        .start_date
+        .as_deref()
+        .and_then(parse_date)
+        .map_or_else(now, |d| d.to_rfc3339());
+    let permits = Arc::new(Semaphore::new(MAX_CONCURRENT_SEARCHES));
+    let mut searches = JoinSet::new();
+    for (i, query) in body.queries.iter().enumerate() {
+        let search_request = SearchRequest::builder()
+            .operation_name(OperationName2::eventSearchWithSeries)
+            .variables(Variables {
+                query: Some(query.clone()),
+                start_date_range: start_date.clone(),
+                first: body.per_query.unwrap_or(40) as i32,
+                ..Default::default()
+            })
+            .build();
+        let permits = permits.clone();
+        searches.spawn(async move {
+            let _permit = permits.acquire_owned().await;
+            return (i, search_request.fetch().await);
+        });
+    }
+
+    info!("Fetching events for {} queries", body.queries.len());
+    let mut results = Vec::with_capacity(body.queries.len());
+    while let Some(result) = searches.join_next().await {
+        match result {
+            Ok(result) => results.push(result),
+            Err(err) => error!("Search task failed: {}", err),
+        }
+    }
+    results.sort_by_key(|(i, _)| *i);
+
+    let mut response = GQLResponse::default();
+    let mut last_error = None;
+    for (i, result) in results {
+        match result {
+            Ok(mut res) => {
+                res.tag_matched_query(&body.queries[i]);
+                response.merge(res);
+            }
+            Err(err) => {
+                error!("Failed to search for `{}`: {}", body.queries[i], err);
+                last_error = Some(err);
+            }
+        }
+    }
+    if response.data.is_none() {
+        return Err(ApiError::Internal(AppError(
+            last_error.unwrap_or(anyhow!("No events found")),
+        )));
+    }
+
+    response.dedup(&body.dedup.unwrap_or_default());
+    response.sort();
+    response.sort_by_matched_queries();
+    if needs_venue_addresses(field_tree.as_ref(), &map_link, &state) {
+        add_venue_addresses(&mut response).await;
+    }
+    map_link.generate_map_urls(&mut response, state.map_provider);
+    response.add_description_fields(&description_fields);
+    info!("Events fetched");
+    return Ok(events_response(
+        response,
+        field_tree.as_ref(),
+        &state.html_sanitizer,
+    ));
 }
 
 // #[instrument(skip_all)]
@@ -343,3 +1207,205 @@ pub async fn search_handler(
 //         );
 //     }
 //
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::meetup::query::request::gql2::{Node, PageInfo, Venue};
+
+    #[test]
+    /// Cursors of the query for each event type are combined into one
+    fn can_combine_cursors() {
+        let cursors = vec![
+            (EventType::Physical, "MTA=".to_string()),
+            (EventType::Hybrid, "NDA=".to_string()),
+        ];
+        let cursor = encode_cursor(&cursors);
+        assert_eq!(decode_cursor(&cursor).unwrap(), cursors);
+        assert!(validate_cursor(Some(EventTypeFilter::Any), Some(&cursor)).is_ok());
+        assert!(validate_cursor(Some(EventTypeFilter::Any), Some("MTA=")).is_err());
+        assert!(validate_cursor(Some(EventTypeFilter::Online), Some("MTA=")).is_ok());
+    }
+
+    #[test]
+    /// Each event type gets a share of the page, and merged events are ordered by the sort field
+    fn can_merge_event_types() {
+        assert_eq!(split_first(20, 3), vec![7, 7, 6]);
+        assert_eq!(split_first(2, 3), vec![1, 1, 1]);
+
+        let event = |id: &str, date_time: &str, lat: f64| Node {
+            venue: Some(Venue {
+                lat,
+                lon: -79.4,
+                ..Default::default()
+            }),
+            ..Node::at(id, date_time)
+        };
+        let page = |nodes: Vec<Node>, end_cursor: Option<&str>| {
+            let mut res = GQLResponse::from_nodes(nodes);
+            res.data.as_mut().unwrap().result.page_info = PageInfo {
+                has_next_page: end_cursor.is_some(),
+                end_cursor: end_cursor.map(String::from),
+            };
+            return res;
+        };
+        let results = || {
+            vec![
+                (
+                    EventType::Physical,
+                    page(
+                        vec![
+                            event("1", "2024-11-22T18:00:00-05:00", 43.7),
+                            event("2", "2024-11-20T18:00:00-05:00", 45.0),
+                        ],
+                        Some("MTA="),
+                    ),
+                ),
+                (
+                    EventType::Online,
+                    page(
+                        vec![Node {
+                            is_online: true,
+                            ..event("3", "2024-11-21T18:00:00-05:00", 0.0)
+                        }],
+                        None,
+                    ),
+                ),
+                (
+                    EventType::Hybrid,
+                    page(
+                        vec![event("4", "2024-11-19T18:00:00-05:00", 43.6)],
+                        Some("NDA="),
+                    ),
+                ),
+            ]
+        };
+        let ids = |res: &GQLResponse| -> Vec<String> {
+            let edges = &res.data.as_ref().unwrap().result.edges;
+            return edges.iter().map(|e| e.node.id.clone()).collect();
+        };
+        let location = (43.6, -79.4);
+
+        let res = merge_event_types(results(), &SortField::Relevance, location);
+        assert_eq!(ids(&res), ["1", "3", "4", "2"]);
+        let result = &res.data.as_ref().unwrap().result;
+        assert_eq!(result.total_count, 4);
+        assert!(result.page_info.has_next_page);
+        assert_eq!(
+            decode_cursor(result.page_info.end_cursor.as_ref().unwrap()).unwrap(),
+            vec![
+                (EventType::Physical, "MTA=".to_string()),
+                (EventType::Hybrid, "NDA=".to_string()),
+            ]
+        );
+
+        let res = merge_event_types(results(), &SortField::DateTime, location);
+        assert_eq!(ids(&res), ["4", "2", "3", "1"]);
+        let res = merge_event_types(results(), &SortField::Distance, location);
+        assert_eq!(ids(&res), ["4", "1", "2", "3"]);
+
+        let res = merge_event_types(
+            vec![(EventType::Online, page(vec![], None))],
+            &SortField::Relevance,
+            location,
+        );
+        let page_info = &res.data.unwrap().result.page_info;
+        assert!(!page_info.has_next_page);
+        assert_eq!(page_info.end_cursor, None);
+    }
+
+    #[test]
+    /// Errors name parameters the way the caller sent them
+    fn can_name_parameters() {
+        assert_eq!(ParamCase::Camel.name("max_distance_km"), "maxDistanceKm");
+        assert_eq!(ParamCase::Snake.name("max_distance_km"), "max_distance_km");
+        assert_eq!(
+            validate_distance(None, None, Some(5.0), None, ParamCase::Camel),
+            Err("`fromLat` and `fromLon` are required to filter or sort by distance".to_string())
+        );
+        assert_eq!(
+            validate_distance(Some(43.6), Some(-79.4), Some(-1.0), None, ParamCase::Snake),
+            Err("`max_distance_km` must not be negative, got -1".to_string())
+        );
+        assert_eq!(
+            validate_series_events(Some(-1), ParamCase::Camel),
+            Err("`seriesEvents` must not be negative, got -1".to_string())
+        );
+        assert!(validate_series_events(Some(0), ParamCase::Snake).is_ok());
+    }
+
+    #[test]
+    /// Discover requests are limited, so one request can't send unbounded searches to Meetup
+    fn can_validate_discover_body() {
+        let validate = |body: serde_json::Value| {
+            return serde_json::from_value::<DiscoverRequestBody>(body)
+                .unwrap()
+                .validate();
+        };
+        assert!(validate(serde_json::json!({
+            "queries": ["rust"],
+            "start_date": "2024-12-01T00:00:00-05:00[US/Eastern]",
+            "per_query": 200
+        }))
+        .is_ok());
+        assert!(validate(serde_json::json!({ "queries": [] })).is_err());
+        assert_eq!(
+            validate(serde_json::json!({ "queries": vec!["rust"; 11] })),
+            Err("At most 10 search queries are allowed, got 11".to_string())
+        );
+        assert_eq!(
+            validate(serde_json::json!({ "queries": ["rust"], "per_query": 4294967295u32 })),
+            Err("`per_query` must be between 1 and 200, got 4294967295".to_string())
+        );
+        assert_eq!(
+            validate(serde_json::json!({ "queries": ["rust"], "start_date": "tomorrow" })),
+            Err("`start_date` `tomorrow` is not a valid RFC 3339 date".to_string())
+        );
+    }
+
+    #[test]
+    /// Search dates are checked, and shortcuts resolved in the time zone of the caller
+    fn can_resolve_search_dates() {
+        let body = |body: serde_json::Value| -> SearchRequestBody {
+            return serde_json::from_value(body).unwrap();
+        };
+        let now = DateTime::parse_from_rfc3339("2024-11-21T03:00:00Z")
+            .unwrap()
+            .to_utc();
+
+        let dates =
+            body(serde_json::json!({ "date_range": "today", "timezone": "America/Vancouver" }))
+                .dates(now)
+                .unwrap();
+        assert_eq!(dates.end.unwrap().to_rfc3339(), "2024-11-20T23:59:59-08:00");
+        let dates = body(serde_json::json!({ "end_date": "2024-12-01T00:00:00-05:00" }))
+            .dates(now)
+            .unwrap();
+        assert_eq!(dates.start, None);
+        assert_eq!(
+            body(serde_json::json!({
+                "start_date": "2024-12-01T00:00:00-05:00",
+                "end_date": "2024-11-30T00:00:00-05:00"
+            }))
+            .dates(now),
+            Err("`end_date` must not be before `start_date`".to_string())
+        );
+        assert!(body(
+            serde_json::json!({ "date_range": "today", "end_date": "2024-12-01T00:00:00Z" })
+        )
+        .dates(now)
+        .is_err());
+        assert!(
+            body(serde_json::json!({ "date_range": "today", "timezone": "Mars/Olympus" }))
+                .dates(now)
+                .is_err()
+        );
+        let dates = body(serde_json::json!({ "when": "tomorrow evening" }))
+            .dates(now)
+            .unwrap();
+        assert_eq!(
+            dates.start.unwrap().to_rfc3339(),
+            "2024-11-21T17:00:00-05:00"
+        );
+    }
+}
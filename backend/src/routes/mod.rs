@@ -1,29 +1,128 @@
+pub mod calendar;
+pub mod category;
+pub mod date;
+pub mod error;
+pub mod export;
+pub mod feed;
 pub mod meetup;
+pub mod response;
+pub mod saved_search;
+pub mod v1;
 
+use crate::event_sequence::EventSequenceStore;
+use crate::map_link::MapProvider;
+use crate::meetup::query::request::category::CategoryCache;
+use crate::routing::TravelTimeRouter;
+use crate::sanitize::HtmlSanitizer;
+use crate::saved_search::SavedSearchStore;
 use axum::Router;
+use calendar::{
+    __path_event_calendar_handler, __path_recommended_calendar_handler,
+    __path_rsvp_calendar_handler,
+};
+use calendar::{event_calendar_handler, recommended_calendar_handler, rsvp_calendar_handler};
+use category::{__path_categories_handler, categories_handler};
 use common_axum::axum::{
     __path_app_version, app_version, attach_tracing_cors_middleware,
     generate_open_api_spec_from_open_api,
 };
-use meetup::{__path_recommended_meetups_handler, __path_search_handler};
-use meetup::{recommended_meetups_handler, search_handler};
+use date::{__path_parse_date_handler, parse_date_handler};
+use feed::{
+    __path_recommended_atom_handler, __path_recommended_rss_handler, __path_search_atom_handler,
+    __path_search_rss_handler,
+};
+use feed::{
+    recommended_atom_handler, recommended_rss_handler, search_atom_handler, search_rss_handler,
+};
+use meetup::{__path_discover_handler, __path_recommended_meetups_handler, __path_search_handler};
+use meetup::{discover_handler, recommended_meetups_handler, search_handler};
+use saved_search::{
+    __path_create_saved_search_handler, __path_delete_saved_search_handler,
+    __path_get_saved_search_handler, __path_list_saved_searches_handler,
+    __path_run_saved_search_handler, __path_update_saved_search_handler,
+};
+use saved_search::{
+    create_saved_search_handler, delete_saved_search_handler, get_saved_search_handler,
+    list_saved_searches_handler, run_saved_search_handler, update_saved_search_handler,
+};
+use std::sync::Arc;
+use tower_http::compression::CompressionLayer;
 use tracing::info;
+use utoipa::openapi::tag::TagBuilder;
 use utoipa_axum::{router::OpenApiRouter, routes};
+use v1::{__path_recommended_events_handler, __path_search_events_handler};
+use v1::{recommended_events_handler, search_events_handler};
+
+/// State shared by all route handlers
+#[derive(Clone)]
+pub struct AppState {
+    /// Searches saved by users
+    pub saved_searches: Arc<SavedSearchStore>,
+    /// Secret the token of each user's saved searches is derived from. Saved searches can't be
+    /// used if not set
+    pub saved_search_secret: Option<String>,
+    /// Revisions of events in calendar feeds
+    pub event_sequences: Arc<EventSequenceStore>,
+    /// Revisions of events in RSS and Atom feeds, kept apart from calendar revisions so feed
+    /// events don't push calendar events out of the store
+    pub feed_revisions: Arc<EventSequenceStore>,
+    /// Secret token of the RSVP calendar feed. The feed is disabled if not set
+    pub rsvp_calendar_token: Option<String>,
+    /// Map app event links go to, unless another is requested
+    pub map_provider: MapProvider,
+    /// Routing service travel times to events are estimated with. Travel times are not
+    /// estimated if not set
+    pub travel_time_router: Option<Arc<TravelTimeRouter>>,
+    /// Cleans event descriptions before they are sent to the browser
+    pub html_sanitizer: HtmlSanitizer,
+    /// Topic categories events can be filtered by
+    pub categories: Arc<CategoryCache>,
+}
 
-pub fn app() -> Router {
+pub fn app(state: AppState) -> Router {
     let (router, mut api_spec) = OpenApiRouter::new()
         .routes(routes!(app_version))
         .routes(routes!(recommended_meetups_handler))
         .routes(routes!(search_handler))
+        .routes(routes!(discover_handler))
+        .routes(routes!(categories_handler))
+        .routes(routes!(parse_date_handler))
+        .routes(routes!(recommended_calendar_handler))
+        .routes(routes!(event_calendar_handler))
+        .routes(routes!(rsvp_calendar_handler))
+        .routes(routes!(recommended_atom_handler))
+        .routes(routes!(recommended_rss_handler))
+        .routes(routes!(search_atom_handler))
+        .routes(routes!(search_rss_handler))
+        .routes(routes!(
+            list_saved_searches_handler,
+            create_saved_search_handler
+        ))
+        .routes(routes!(
+            get_saved_search_handler,
+            update_saved_search_handler,
+            delete_saved_search_handler
+        ))
+        .routes(routes!(run_saved_search_handler))
+        .routes(routes!(recommended_events_handler))
+        .routes(routes!(search_events_handler))
+        .with_state(state)
         .split_for_parts();
 
     api_spec.info.title = "freshmeet backend".to_string();
     api_spec.info.description = None;
     api_spec.info.contact = None;
     api_spec.info.license = None;
+    api_spec.tags = Some(vec![TagBuilder::new()
+        .name("v1")
+        .description(Some(
+            "Version 1 of the FreshMeet API. Responses only change with a new version",
+        ))
+        .build()]);
 
     generate_open_api_spec_from_open_api(api_spec, "open_api_spec.json")
         .expect("Failed to generate open API spec");
     info!("Generated open api spec");
-    return attach_tracing_cors_middleware(router);
+    // Compress responses with the best encoding the client accepts
+    return attach_tracing_cors_middleware(router).layer(CompressionLayer::new());
 }
@@ -0,0 +1,139 @@
+//! JSON responses of events
+
+use crate::fields::{project, FieldTree};
+use crate::meetup::query::request::gql2::{GQLResponse, MeetupResult};
+use crate::sanitize::HtmlSanitizer;
+use axum::{
+    body::Body,
+    http::header,
+    response::{IntoResponse, Response},
+    Json,
+};
+use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
+use std::convert::Infallible;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+
+/// Number of events formatted and sent at a time in a streamed response
+const EVENTS_PER_CHUNK: usize = 20;
+
+/// Respond with the events in `res`, formatted with `sanitizer`.
+///
+/// If `fields` are requested, events are projected to them and the response is sent all at once.
+/// Otherwise events are formatted and serialized a chunk at a time while the response is being
+/// sent, so the first bytes go out before every event is formatted
+pub fn events_response(
+    mut res: GQLResponse,
+    fields: Option<&FieldTree>,
+    sanitizer: &HtmlSanitizer,
+) -> Response {
+    // Formatting an invalid start date would panic, cutting off a streamed response
+    res.skip_invalid_start_dates();
+    if let Some(fields) = fields {
+        res.format(sanitizer);
+        return Json(project(&res, fields)).into_response();
+    }
+    let Some(data) = res.data.take() else {
+        return Json(res).into_response();
+    };
+    let MeetupResult {
+        page_info,
+        total_count,
+        mut edges,
+    } = data.result;
+    // Same layout as serializing the whole response
+    let head = format!(
+        r#"{{"data":{{"result":{{"pageInfo":{page_info},"totalCount":{total_count},"edges":["#,
+        page_info = serde_json::to_string(&page_info).expect("Page info is always valid JSON"),
+    );
+    let tail = format!(
+        r#"]}}}},"errors":{errors}}}"#,
+        errors = serde_json::to_string(&res.errors).expect("Errors are always valid JSON"),
+    );
+
+    let (tx, rx) = mpsc::channel::<Result<String, Infallible>>(4);
+    let sanitizer = sanitizer.clone();
+    tokio::task::spawn_blocking(move || {
+        if tx.blocking_send(Ok(head)).is_err() {
+            return;
+        }
+        for (i, chunk) in edges.chunks_mut(EVENTS_PER_CHUNK).enumerate() {
+            chunk
+                .par_iter_mut()
+                .map(|edge| {
+                    edge.description_to_html(&sanitizer);
+                    edge.format_start_date();
+                    edge.is_attending_to_str();
+                })
+                .for_each(drop);
+            let mut json = if i == 0 {
+                String::new()
+            } else {
+                ",".to_string()
+            };
+            json.push_str(
+                &chunk
+                    .iter()
+                    .map(|edge| serde_json::to_string(edge).expect("Events are always valid JSON"))
+                    .collect::<Vec<String>>()
+                    .join(","),
+            );
+            if tx.blocking_send(Ok(json)).is_err() {
+                // Client went away
+                return;
+            }
+        }
+        let _ = tx.blocking_send(Ok(tail));
+    });
+
+    return (
+        [(header::CONTENT_TYPE, "application/json")],
+        Body::from_stream(ReceiverStream::new(rx)),
+    )
+        .into_response();
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::meetup::query::request::gql2::{Edge, GQLData, Node};
+
+    #[tokio::test]
+    /// A streamed response is the same as serializing the whole formatted response
+    async fn can_stream_events() {
+        let edges = (0..45)
+            .map(|i| Edge {
+                node: Node {
+                    id: i.to_string(),
+                    title: format!("Event {i}"),
+                    description: "**Talks** and <script>alert(1)</script>".into(),
+                    date_time: "2024-12-01T18:00:00-05:00".into(),
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .collect();
+        let mut res = GQLResponse {
+            data: Some(GQLData {
+                result: MeetupResult {
+                    total_count: 45,
+                    edges,
+                    ..Default::default()
+                },
+            }),
+            ..Default::default()
+        };
+        let sanitizer = HtmlSanitizer::default();
+        let mut formatted = res.clone();
+        formatted.format(&sanitizer);
+
+        // Events with an invalid start date are skipped instead of cutting off the response
+        res.data.as_mut().unwrap().result.edges[44].node.date_time = "Sunday".into();
+        formatted.data.as_mut().unwrap().result.edges.pop();
+
+        let body = events_response(res, None, &sanitizer).into_body();
+        let bytes = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+        let streamed: GQLResponse = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(streamed, formatted);
+    }
+}
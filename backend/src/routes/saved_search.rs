@@ -0,0 +1,283 @@
+//! Route handlers for saved searches. Every route requires the token of the user in an
+//! `Authorization: Bearer <token>` header, see `user_token`
+
+use crate::fields::FieldsQueryParams;
+use crate::map_link::MapLinkQueryParams;
+use crate::meetup::query::request::gql2::GQLResponse;
+use crate::meetup::query::request::venue::add_venue_addresses;
+use crate::routes::error::ApiError;
+use crate::routes::meetup::{estimate_travel_times, needs_venue_addresses, search};
+use crate::routes::response::events_response;
+use crate::routes::AppState;
+use crate::saved_search::{user_token, SavedSearchBody, SavedSearchResponse};
+use crate::utils::constant_time_eq;
+use axum::{
+    extract::{Path, Query, State},
+    http::{header::AUTHORIZATION, HeaderMap, StatusCode},
+    response::Response,
+    Json,
+};
+use common_axum::axum::AppError;
+use tracing::error;
+
+/// Lists saved searches of a user
+#[utoipa::path(
+    get,
+    path = "/users/{user}/searches",
+    responses(
+        (status = 200, description = "Successfully listed saved searches", body = Vec<SavedSearchResponse>),
+        (status = 401, description = "Missing or invalid token of the user", body = String)
+    ),
+    params(
+        ("user" = String, Path, description = "User the searches belong to"),
+        ("Authorization" = String, Header, description = "`Bearer` token of the user")
+    )
+)]
+pub async fn list_saved_searches_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(user): Path<String>,
+) -> Result<Json<Vec<SavedSearchResponse>>, ApiError> {
+    authorize(state.saved_search_secret.as_deref(), &headers, &user)?;
+    return Ok(Json(
+        state
+            .saved_searches
+            .list(&user)
+            .await
+            .into_iter()
+            .map(SavedSearchResponse::from)
+            .collect(),
+    ));
+}
+
+/// Saves a new search for a user
+#[utoipa::path(
+    post,
+    path = "/users/{user}/searches",
+    responses(
+        (status = 201, description = "Successfully saved search", body = SavedSearchResponse),
+        (status = 400, description = "Invalid search", body = String),
+        (status = 401, description = "Missing or invalid token of the user", body = String),
+        (status = 500, description = "Failed to save search", body = String)
+    ),
+    params(
+        ("user" = String, Path, description = "User the search belongs to"),
+        ("Authorization" = String, Header, description = "`Bearer` token of the user")
+    ),
+    request_body = SavedSearchBody
+)]
+pub async fn create_saved_search_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(user): Path<String>,
+    Json(body): Json<SavedSearchBody>,
+) -> Result<(StatusCode, Json<SavedSearchResponse>), ApiError> {
+    authorize(state.saved_search_secret.as_deref(), &headers, &user)?;
+    body.validate().map_err(ApiError::BadRequest)?;
+    match state.saved_searches.create(&user, body).await {
+        Ok(saved_search) => Ok((StatusCode::CREATED, Json(saved_search.into()))),
+        Err(err) => {
+            error!("Failed to save search: {}", err);
+            Err(ApiError::Internal(AppError(err)))
+        }
+    }
+}
+
+/// Gets a saved search of a user
+#[utoipa::path(
+    get,
+    path = "/users/{user}/searches/{id}",
+    responses(
+        (status = 200, description = "Found saved search", body = SavedSearchResponse),
+        (status = 401, description = "Missing or invalid token of the user", body = String),
+        (status = 404, description = "Saved search does not exist", body = String)
+    ),
+    params(
+        ("user" = String, Path, description = "User the search belongs to"),
+        ("id" = u64, Path, description = "ID of the saved search"),
+        ("Authorization" = String, Header, description = "`Bearer` token of the user")
+    )
+)]
+pub async fn get_saved_search_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path((user, id)): Path<(String, u64)>,
+) -> Result<Json<SavedSearchResponse>, ApiError> {
+    authorize(state.saved_search_secret.as_deref(), &headers, &user)?;
+    return match state.saved_searches.get(&user, id).await {
+        Some(saved_search) => Ok(Json(saved_search.into())),
+        None => Err(not_found(id)),
+    };
+}
+
+/// Updates a saved search of a user
+#[utoipa::path(
+    put,
+    path = "/users/{user}/searches/{id}",
+    responses(
+        (status = 200, description = "Successfully updated saved search", body = SavedSearchResponse),
+        (status = 400, description = "Invalid search", body = String),
+        (status = 401, description = "Missing or invalid token of the user", body = String),
+        (status = 404, description = "Saved search does not exist", body = String),
+        (status = 500, description = "Failed to update saved search", body = String)
+    ),
+    params(
+        ("user" = String, Path, description = "User the search belongs to"),
+        ("id" = u64, Path, description = "ID of the saved search"),
+        ("Authorization" = String, Header, description = "`Bearer` token of the user")
+    ),
+    request_body = SavedSearchBody
+)]
+pub async fn update_saved_search_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path((user, id)): Path<(String, u64)>,
+    Json(body): Json<SavedSearchBody>,
+) -> Result<Json<SavedSearchResponse>, ApiError> {
+    authorize(state.saved_search_secret.as_deref(), &headers, &user)?;
+    body.validate().map_err(ApiError::BadRequest)?;
+    match state.saved_searches.update(&user, id, body).await {
+        Ok(Some(saved_search)) => Ok(Json(saved_search.into())),
+        Ok(None) => Err(not_found(id)),
+        Err(err) => {
+            error!("Failed to update saved search: {}", err);
+            Err(ApiError::Internal(AppError(err)))
+        }
+    }
+}
+
+/// Deletes a saved search of a user
+#[utoipa::path(
+    delete,
+    path = "/users/{user}/searches/{id}",
+    responses(
+        (status = 204, description = "Successfully deleted saved search"),
+        (status = 401, description = "Missing or invalid token of the user", body = String),
+        (status = 404, description = "Saved search does not exist", body = String),
+        (status = 500, description = "Failed to delete saved search", body = String)
+    ),
+    params(
+        ("user" = String, Path, description = "User the search belongs to"),
+        ("id" = u64, Path, description = "ID of the saved search"),
+        ("Authorization" = String, Header, description = "`Bearer` token of the user")
+    )
+)]
+pub async fn delete_saved_search_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path((user, id)): Path<(String, u64)>,
+) -> Result<StatusCode, ApiError> {
+    authorize(state.saved_search_secret.as_deref(), &headers, &user)?;
+    match state.saved_searches.delete(&user, id).await {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err(not_found(id)),
+        Err(err) => {
+            error!("Failed to delete saved search: {}", err);
+            Err(ApiError::Internal(AppError(err)))
+        }
+    }
+}
+
+/// Runs a saved search of a user
+#[utoipa::path(
+    get,
+    path = "/users/{user}/searches/{id}/run",
+    responses(
+        (status = 200, description = "Successfully returned searched meetups", body = GQLResponse),
+        (status = 400, description = "Saved search is invalid", body = String),
+        (status = 401, description = "Missing or invalid token of the user", body = String),
+        (status = 404, description = "Saved search does not exist", body = String),
+        (status = 500, description = "Failed to search for meetups", body = String)
+    ),
+    params(
+        ("user" = String, Path, description = "User the search belongs to"),
+        ("id" = u64, Path, description = "ID of the saved search"),
+        ("Authorization" = String, Header, description = "`Bearer` token of the user"),
+        MapLinkQueryParams,
+        FieldsQueryParams
+    )
+)]
+pub async fn run_saved_search_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path((user, id)): Path<(String, u64)>,
+    Query(map_link): Query<MapLinkQueryParams>,
+    Query(fields): Query<FieldsQueryParams>,
+) -> Result<Response, ApiError> {
+    authorize(state.saved_search_secret.as_deref(), &headers, &user)?;
+    let field_tree = fields.field_tree().map_err(ApiError::BadRequest)?;
+    let description_fields = fields.description_fields().map_err(ApiError::BadRequest)?;
+    let Some(saved_search) = state.saved_searches.get(&user, id).await else {
+        return Err(not_found(id));
+    };
+    saved_search.validate().map_err(ApiError::BadRequest)?;
+    let search_body = saved_search.search;
+    let from = search_body.from_lat.zip(search_body.from_lon);
+    let travel_modes = search_body.travel_modes.clone().unwrap_or_default();
+    match search(search_body).await {
+        Ok(mut res) => {
+            if needs_venue_addresses(field_tree.as_ref(), &map_link, &state) {
+                add_venue_addresses(&mut res).await;
+            }
+            estimate_travel_times(&state, &mut res, from, &travel_modes).await;
+            map_link.generate_map_urls(&mut res, state.map_provider);
+            res.add_description_fields(&description_fields);
+            Ok(events_response(
+                res,
+                field_tree.as_ref(),
+                &state.html_sanitizer,
+            ))
+        }
+        Err(err) => {
+            error!("Failed to run saved search {}: {}", id, err);
+            Err(ApiError::Internal(AppError(err)))
+        }
+    }
+}
+
+/// Check `headers` has the token of `user`, derived from `secret`. Nobody is authorized if no
+/// secret is configured
+fn authorize(secret: Option<&str>, headers: &HeaderMap, user: &str) -> Result<(), ApiError> {
+    let token = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    let authorized = match (secret, token) {
+        (Some(secret), Some(token)) => {
+            constant_time_eq(user_token(secret, user).as_bytes(), token.trim().as_bytes())
+        }
+        _ => false,
+    };
+    if !authorized {
+        return Err(ApiError::Unauthorized(format!(
+            "A valid token of `{user}` is required"
+        )));
+    }
+    return Ok(());
+}
+
+/// Error for a saved search that does not exist
+fn not_found(id: u64) -> ApiError {
+    return ApiError::NotFound(format!("Saved search {id} does not exist"));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    /// Users can only use their own saved searches, with the token derived from the secret
+    fn can_authorize_users() {
+        let headers = |token: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(AUTHORIZATION, format!("Bearer {token}").parse().unwrap());
+            return headers;
+        };
+        let alice = user_token("secret", "alice");
+        assert!(authorize(Some("secret"), &headers(&alice), "alice").is_ok());
+        assert!(authorize(Some("secret"), &headers(&alice), "bob").is_err());
+        assert!(authorize(Some("other"), &headers(&alice), "alice").is_err());
+        assert!(authorize(None, &headers(&alice), "alice").is_err());
+        assert!(authorize(Some("secret"), &HeaderMap::new(), "alice").is_err());
+    }
+}
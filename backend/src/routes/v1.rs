@@ -0,0 +1,105 @@
+//! Version 1 of the FreshMeet API. Responses only change with a new API version
+
+use crate::dto::{EventsDto, RecommendedEventsQueryDto, SearchEventsBodyDto};
+use crate::map_link::MapLinkQueryParams;
+use crate::meetup::query::request::gql2::GQLResponse;
+use crate::meetup::query::request::venue::add_venue_addresses;
+use crate::routes::error::ApiError;
+use crate::routes::meetup::{
+    estimate_travel_times, recommended, search, RecommendedMeetupsQueryParams, SearchRequestBody,
+};
+use crate::routes::AppState;
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use common_axum::axum::AppError;
+use tracing::error;
+
+/// Gets recommended events
+#[utoipa::path(
+    get,
+    path = "/v1/events/recommended",
+    tag = "v1",
+    responses(
+        (status = 200, description = "Found recommended events successfully", body = EventsDto),
+        (status = 400, description = "Invalid query parameters", body = String),
+        (status = 500, description = "Failed to fetch events", body = String)
+    ),
+    params(
+        RecommendedEventsQueryDto,
+        MapLinkQueryParams
+    )
+)]
+pub async fn recommended_events_handler(
+    State(state): State<AppState>,
+    Query(query): Query<RecommendedEventsQueryDto>,
+    Query(map_link): Query<MapLinkQueryParams>,
+) -> Result<Json<EventsDto>, ApiError> {
+    let query = RecommendedMeetupsQueryParams::from(query);
+    query.validate().map_err(ApiError::BadRequest)?;
+    match recommended(&query).await {
+        Ok(mut res) => {
+            let travel_modes = query.travel_modes().unwrap_or_default();
+            let from = query.from_lat.zip(query.from_lon);
+            estimate_travel_times(&state, &mut res, from, &travel_modes).await;
+            Ok(Json(events(&state, res, &map_link).await))
+        }
+        Err(e) => {
+            error!("Error: {}", e);
+            Err(ApiError::Internal(AppError(e)))
+        }
+    }
+}
+
+/// Searches events
+#[utoipa::path(
+    post,
+    path = "/v1/events/search",
+    tag = "v1",
+    responses(
+        (status = 200, description = "Successfully returned searched events", body = EventsDto),
+        (status = 400, description = "Invalid search parameters", body = String),
+        (status = 500, description = "Failed to search for events", body = String)
+    ),
+    params(
+        MapLinkQueryParams
+    ),
+    request_body = SearchEventsBodyDto
+)]
+pub async fn search_events_handler(
+    State(state): State<AppState>,
+    Query(map_link): Query<MapLinkQueryParams>,
+    Json(body): Json<SearchEventsBodyDto>,
+) -> Result<Json<EventsDto>, ApiError> {
+    let body = SearchRequestBody::from(body);
+    body.validate().map_err(ApiError::BadRequest)?;
+    let from = body.from_lat.zip(body.from_lon);
+    let travel_modes = body.travel_modes.clone().unwrap_or_default();
+    match search(body).await {
+        Ok(mut res) => {
+            estimate_travel_times(&state, &mut res, from, &travel_modes).await;
+            Ok(Json(events(&state, res, &map_link).await))
+        }
+        Err(err) => {
+            error!("Error: {}", err);
+            Err(ApiError::Internal(AppError(err)))
+        }
+    }
+}
+
+/// Convert the events in `res`, with venue addresses, map links and sanitized descriptions
+async fn events(
+    state: &AppState,
+    mut res: GQLResponse,
+    map_link: &MapLinkQueryParams,
+) -> EventsDto {
+    add_venue_addresses(&mut res).await;
+    map_link.generate_map_urls(&mut res, state.map_provider);
+    if let Some(data) = res.data.as_mut() {
+        for edge in &mut data.result.edges {
+            edge.description_to_html(&state.html_sanitizer);
+        }
+    }
+    return EventsDto::from(res);
+}
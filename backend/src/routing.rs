@@ -0,0 +1,350 @@
+//! Travel time estimates from a self-hosted OSRM or Valhalla compatible routing service
+
+use crate::map_link::TravelMode;
+use crate::meetup::query::request::gql2::GQLResponse;
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{RwLock, Semaphore};
+use tokio::task::JoinSet;
+use tracing::{debug, error};
+
+/// How long a travel time is cached for
+const CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+/// Maximum number of cached travel times. The cache is cleared when full
+const MAX_CACHE_ENTRIES: usize = 10_000;
+/// How long to stop asking the routing service for routes after it failed
+const UNAVAILABLE_BACKOFF: Duration = Duration::from_secs(60);
+/// Timeout of a single route request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+/// Maximum number of route requests sent at the same time
+const MAX_CONCURRENT_ROUTES: usize = 8;
+
+/// API spoken by the routing service
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RouterKind {
+    Osrm,
+    Valhalla,
+}
+
+impl FromStr for RouterKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s {
+            "osrm" => Ok(RouterKind::Osrm),
+            "valhalla" => Ok(RouterKind::Valhalla),
+            _ => Err(format!(
+                "Unknown router kind `{s}`, expected osrm or valhalla"
+            )),
+        };
+    }
+}
+
+/// Cache key of a route. Coordinates are rounded to about 10 metres, so nearby starting points
+/// share travel times
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct RouteKey {
+    from: (i64, i64),
+    to: (i64, i64),
+    mode: TravelMode,
+}
+
+impl RouteKey {
+    fn new(from: (f64, f64), to: (f64, f64), mode: TravelMode) -> Self {
+        let round =
+            |(lat, lon): (f64, f64)| ((lat * 1e4).round() as i64, (lon * 1e4).round() as i64);
+        return Self {
+            from: round(from),
+            to: round(to),
+            mode,
+        };
+    }
+}
+
+/// Estimates travel times with a routing service, caching the results.
+///
+/// If the routing service is unreachable, no travel times are returned for a while instead of
+/// failing requests
+pub struct TravelTimeRouter {
+    base_url: String,
+    kind: RouterKind,
+    client: reqwest::Client,
+    /// Travel time in minutes by route, and when it was fetched. `None` if there is no route
+    cache: RwLock<HashMap<RouteKey, (Instant, Option<u32>)>>,
+    /// The routing service is not asked for routes until this time
+    unavailable_until: RwLock<Option<Instant>>,
+}
+
+#[derive(Deserialize)]
+struct OsrmResponse {
+    code: String,
+    #[serde(default)]
+    routes: Vec<OsrmRoute>,
+}
+
+#[derive(Deserialize)]
+struct OsrmRoute {
+    /// Travel time in seconds
+    duration: f64,
+}
+
+#[derive(Deserialize)]
+struct ValhallaResponse {
+    trip: ValhallaTrip,
+}
+
+#[derive(Deserialize)]
+struct ValhallaTrip {
+    summary: ValhallaSummary,
+}
+
+#[derive(Deserialize)]
+struct ValhallaSummary {
+    /// Travel time in seconds
+    time: f64,
+}
+
+impl TravelTimeRouter {
+    /// Create a router for the routing service at `base_url`
+    pub fn new(base_url: impl Into<String>, kind: RouterKind) -> Self {
+        return Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            kind,
+            client: reqwest::Client::new(),
+            cache: RwLock::new(HashMap::new()),
+            unavailable_until: RwLock::new(None),
+        };
+    }
+
+    /// Minutes it takes to travel from `from` to `to` by `mode`, both given as `(lat, lon)`.
+    /// `None` if there is no route, or the routing service is unavailable
+    pub async fn travel_minutes(
+        &self,
+        from: (f64, f64),
+        to: (f64, f64),
+        mode: TravelMode,
+    ) -> Option<u32> {
+        let key = RouteKey::new(from, to, mode);
+        if let Some((fetched, minutes)) = self.cache.read().await.get(&key) {
+            if fetched.elapsed() < CACHE_TTL {
+                return *minutes;
+            }
+        }
+        if self
+            .unavailable_until
+            .read()
+            .await
+            .is_some_and(|until| Instant::now() < until)
+        {
+            return None;
+        }
+
+        let seconds = match self.kind {
+            RouterKind::Osrm => self.fetch_osrm(from, to, mode).await,
+            RouterKind::Valhalla => self.fetch_valhalla(from, to, mode).await,
+        };
+        let minutes = match seconds {
+            Ok(seconds) => seconds.map(|s| (s / 60.0).round() as u32),
+            Err(e) => {
+                error!("Routing service at {} is unavailable: {}", self.base_url, e);
+                *self.unavailable_until.write().await = Some(Instant::now() + UNAVAILABLE_BACKOFF);
+                return None;
+            }
+        };
+        let mut cache = self.cache.write().await;
+        if cache.len() >= MAX_CACHE_ENTRIES {
+            cache.clear();
+        }
+        cache.insert(key, (Instant::now(), minutes));
+        return minutes;
+    }
+
+    /// Set the travel time by each of `modes` from `from` to every in person event in `res`
+    pub async fn annotate(
+        self: &Arc<Self>,
+        res: &mut GQLResponse,
+        from: (f64, f64),
+        modes: &[TravelMode],
+    ) {
+        let Some(data) = res.data.as_mut() else {
+            return;
+        };
+        let edges = &mut data.result.edges;
+        let semaphore = Arc::new(Semaphore::new(MAX_CONCURRENT_ROUTES));
+        let mut routes = JoinSet::new();
+        for (i, edge) in edges.iter().enumerate() {
+            let Some(venue) = edge.node.venue.as_ref().filter(|_| !edge.node.is_online) else {
+                continue;
+            };
+            for &mode in modes {
+                let router = self.clone();
+                let semaphore = semaphore.clone();
+                let to = (venue.lat, venue.lon);
+                routes.spawn(async move {
+                    let _permit = semaphore.acquire_owned().await;
+                    return (i, mode, router.travel_minutes(from, to, mode).await);
+                });
+            }
+        }
+
+        let mut travel_minutes: HashMap<usize, BTreeMap<TravelMode, u32>> = HashMap::new();
+        while let Some(route) = routes.join_next().await {
+            match route {
+                // Events without any route keep no travel times, ie when the router is down
+                Ok((i, mode, Some(minutes))) => {
+                    travel_minutes.entry(i).or_default().insert(mode, minutes);
+                }
+                Ok((_, _, None)) => {}
+                Err(e) => error!("Error: {}", e),
+            }
+        }
+        debug!("Estimated travel times to {} events", travel_minutes.len());
+        for (i, minutes) in travel_minutes {
+            edges[i].node.travel_minutes = Some(minutes);
+        }
+    }
+
+    /// Travel time in seconds from an OSRM service. The profile is picked by the travel mode,
+    /// OSRM has no transit routing
+    async fn fetch_osrm(
+        &self,
+        from: (f64, f64),
+        to: (f64, f64),
+        mode: TravelMode,
+    ) -> Result<Option<f64>> {
+        let profile = match mode {
+            TravelMode::Driving => "car",
+            TravelMode::Walking => "foot",
+            TravelMode::Cycling => "bike",
+            TravelMode::Transit => return Ok(None),
+        };
+        let url = format!(
+            "{base}/route/v1/{profile}/{from_lon},{from_lat};{to_lon},{to_lat}?overview=false",
+            base = self.base_url,
+            from_lat = from.0,
+            from_lon = from.1,
+            to_lat = to.0,
+            to_lon = to.1,
+        );
+        let res = self.client.get(url).timeout(REQUEST_TIMEOUT).send().await?;
+        if res.status().is_server_error() {
+            return Err(anyhow!("OSRM responded with {}", res.status()));
+        }
+        // OSRM responds with a client error and a code, ie `NoRoute`, when there is no route
+        let body: OsrmResponse = res.json().await?;
+        if body.code != "Ok" {
+            debug!("No OSRM route: {}", body.code);
+            return Ok(None);
+        }
+        return Ok(body.routes.first().map(|r| r.duration));
+    }
+
+    /// Travel time in seconds from a Valhalla service
+    async fn fetch_valhalla(
+        &self,
+        from: (f64, f64),
+        to: (f64, f64),
+        mode: TravelMode,
+    ) -> Result<Option<f64>> {
+        let costing = match mode {
+            TravelMode::Driving => "auto",
+            TravelMode::Walking => "pedestrian",
+            TravelMode::Cycling => "bicycle",
+            TravelMode::Transit => "multimodal",
+        };
+        let body = json!({
+            "locations": [
+                { "lat": from.0, "lon": from.1 },
+                { "lat": to.0, "lon": to.1 },
+            ],
+            "costing": costing,
+        });
+        let res = self
+            .client
+            .post(format!("{}/route", self.base_url))
+            .json(&body)
+            .timeout(REQUEST_TIMEOUT)
+            .send()
+            .await?;
+        if res.status().is_server_error() {
+            return Err(anyhow!("Valhalla responded with {}", res.status()));
+        }
+        // Valhalla responds with a client error when there is no route
+        if res.status().is_client_error() {
+            debug!(
+                "No Valhalla route: {}",
+                res.text().await.unwrap_or_default()
+            );
+            return Ok(None);
+        }
+        let body: ValhallaResponse = res.json().await?;
+        return Ok(Some(body.trip.summary.time));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::{routing::get, Json, Router};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    /// Travel times are fetched from a stand in OSRM service, and cached
+    async fn can_fetch_travel_minutes() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        let osrm = Router::new().route(
+            "/route/v1/:profile/:coordinates",
+            get(move || async move {
+                counter.fetch_add(1, Ordering::SeqCst);
+                Json(json!({ "code": "Ok", "routes": [{ "duration": 899.6 }] }))
+            }),
+        );
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, osrm).await });
+
+        let router = TravelTimeRouter::new(format!("http://{addr}/"), RouterKind::Osrm);
+        let (from, to) = ((43.6534, -79.3841), (43.6453, -79.3806));
+        assert_eq!(
+            router.travel_minutes(from, to, TravelMode::Walking).await,
+            Some(15)
+        );
+        assert_eq!(
+            router.travel_minutes(from, to, TravelMode::Walking).await,
+            Some(15)
+        );
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+        // OSRM can't route by transit
+        assert_eq!(
+            router.travel_minutes(from, to, TravelMode::Transit).await,
+            None
+        );
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    /// No travel times are returned when the routing service is unreachable
+    async fn can_degrade_when_unreachable() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        let router = TravelTimeRouter::new(format!("http://{addr}"), RouterKind::Valhalla);
+        let minutes = router
+            .travel_minutes(
+                (43.6534, -79.3841),
+                (43.6453, -79.3806),
+                TravelMode::Driving,
+            )
+            .await;
+        assert_eq!(minutes, None);
+        assert!(router.unavailable_until.read().await.is_some());
+    }
+}
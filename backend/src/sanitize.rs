@@ -0,0 +1,179 @@
+//! Sanitize HTML written by event organisers before it is sent to the browser
+
+use ammonia::{Builder, UrlRelative};
+use std::collections::HashSet;
+use std::sync::Arc;
+use urlencoding::encode;
+
+/// Tags kept in event descriptions. Everything else is removed, keeping its text
+const ALLOWED_TAGS: [&str; 22] = [
+    "a",
+    "b",
+    "blockquote",
+    "br",
+    "code",
+    "del",
+    "em",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "hr",
+    "i",
+    "li",
+    "ol",
+    "p",
+    "pre",
+    "s",
+    "strong",
+    "ul",
+];
+
+/// Cleans untrusted HTML so it can be rendered in the browser.
+///
+/// Only an allowlist of tags and attributes is kept. Links open in a new tab without access to
+/// this page, and are not endorsed to search engines. Images are loaded through the image proxy
+/// if one is configured, so users' browsers don't make requests to organisers' servers, and are
+/// removed otherwise
+#[derive(Debug, Clone)]
+pub struct HtmlSanitizer {
+    /// Cleaner built once, and shared by every clone of this sanitizer
+    builder: Arc<Builder<'static>>,
+}
+
+impl Default for HtmlSanitizer {
+    fn default() -> Self {
+        return Self::new(None);
+    }
+}
+
+impl HtmlSanitizer {
+    /// Build a sanitizer loading images through the image proxy at `image_proxy_url`. The image
+    /// URL is passed in the `url` query parameter
+    pub fn new(image_proxy_url: Option<String>) -> Self {
+        let mut tags: HashSet<&str> = ALLOWED_TAGS.into_iter().collect();
+        if image_proxy_url.is_some() {
+            tags.insert("img");
+        }
+        let mut builder = Builder::default();
+        builder
+            .tags(tags)
+            .generic_attributes(HashSet::new())
+            .tag_attributes(
+                [
+                    ("a", ["href", "title"].into_iter().collect()),
+                    ("img", ["src", "alt", "title"].into_iter().collect()),
+                ]
+                .into_iter()
+                .collect(),
+            )
+            .url_schemes(["http", "https", "mailto"].into_iter().collect())
+            .url_relative(UrlRelative::Deny)
+            .link_rel(Some("noopener nofollow"))
+            .set_tag_attribute_value("a", "target", "_blank")
+            .attribute_filter(move |element, attribute, value| {
+                if element != "img" || attribute != "src" {
+                    return Some(value.into());
+                }
+                return proxy_image_url(image_proxy_url.as_deref(), value).map(Into::into);
+            });
+        return Self {
+            builder: Arc::new(builder),
+        };
+    }
+
+    /// Clean `html`, removing anything that isn't allowed
+    pub fn clean(&self, html: &str) -> String {
+        return self.builder.clean(html).to_string();
+    }
+}
+
+/// URL to load the image at `url` through the image `proxy`. `None` if images are not proxied, or
+/// `url` isn't a web URL
+fn proxy_image_url(proxy: Option<&str>, url: &str) -> Option<String> {
+    let proxy = proxy?;
+    let url_lowercase = url.to_lowercase();
+    if !url_lowercase.starts_with("https://") && !url_lowercase.starts_with("http://") {
+        return None;
+    }
+    let separator = if proxy.contains('?') { '&' } else { '?' };
+    return Some(format!("{proxy}{separator}url={}", encode(url)));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    /// Script can't be run from a description
+    fn can_remove_xss_payloads() {
+        let sanitizer = HtmlSanitizer::default();
+        let payloads = [
+            "<script>alert(1)</script>",
+            "<img src=x onerror=alert(1)>",
+            "<svg onload=alert(1)>",
+            "<a href=\"javascript:alert(1)\">click</a>",
+            "<a href=\"JaVaScRiPt:alert(1)\">click</a>",
+            "<a href=\"data:text/html;base64,PHNjcmlwdD5hbGVydCgxKTwvc2NyaXB0Pg==\">click</a>",
+            "<p onclick=\"alert(1)\" style=\"background:url(javascript:alert(1))\">hi</p>",
+            "<iframe src=\"https://evil.example\"></iframe>",
+            "<math><mtext><table><mglyph><style><img src=x onerror=alert(1)>",
+            "<a href=\"/relative\">relative</a>",
+            "<form action=\"https://evil.example\"><input name=password></form>",
+        ];
+        for payload in payloads {
+            let html = sanitizer.clean(payload).to_lowercase();
+            for forbidden in [
+                "<script",
+                "onerror",
+                "onload",
+                "onclick",
+                "javascript:",
+                "data:",
+                "<iframe",
+                "<svg",
+                "<img",
+                "style",
+                "<form",
+                "<input",
+                "/relative",
+            ] {
+                assert!(
+                    !html.contains(forbidden),
+                    "`{payload}` was cleaned to `{html}`, which contains `{forbidden}`"
+                );
+            }
+        }
+    }
+
+    #[test]
+    /// Links open in a new tab, and formatting is kept
+    fn can_rewrite_links() {
+        let html = HtmlSanitizer::default().clean(
+            "<p><strong>Talks</strong> at <a href=\"https://example.com\" rel=\"opener\" target=\"_self\">example</a></p>",
+        );
+        assert_eq!(
+            html,
+            "<p><strong>Talks</strong> at <a href=\"https://example.com\" target=\"_blank\" rel=\"noopener nofollow\">example</a></p>"
+        );
+    }
+
+    #[test]
+    /// Images are proxied if a proxy is configured, and removed otherwise
+    fn can_proxy_images() {
+        let image = "<img src=\"https://example.com/a.png?size=2\" alt=\"Venue\">";
+        assert_eq!(HtmlSanitizer::default().clean(image), "");
+
+        let sanitizer = HtmlSanitizer::new(Some("https://images.freshmeet.example/proxy".into()));
+        assert_eq!(
+            sanitizer.clean(image),
+            "<img src=\"https://images.freshmeet.example/proxy?url=https%3A%2F%2Fexample.com%2Fa.png%3Fsize%3D2\" alt=\"Venue\">"
+        );
+        assert_eq!(
+            sanitizer.clean("<img src=\"ftp://example.com/a.png\">"),
+            "<img>"
+        );
+    }
+}
@@ -0,0 +1,497 @@
+//! Saved searches, persisted to a TOML file on disk
+
+use crate::routes::meetup::SearchRequestBody;
+use anyhow::{anyhow, Result};
+use hmac::{Hmac, Mac};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::HashSet;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::PathBuf;
+use tokio::sync::RwLock;
+use tracing::info;
+use utoipa::ToSchema;
+
+/// A named search belonging to a user
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
+pub struct SavedSearch {
+    /// ID of this saved search
+    pub id: u64,
+    /// User this search belongs to
+    pub user: String,
+    /// Name of this search
+    pub name: String,
+    /// Webhook to notify when new events are found by this search
+    pub webhook: Option<Webhook>,
+    /// Parameters of the search
+    #[serde(flatten)]
+    pub search: SearchRequestBody,
+}
+
+/// A webhook new events are posted to. Compatible with Slack and Discord incoming webhooks
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, PartialEq)]
+pub struct Webhook {
+    /// URL to post new events to
+    pub url: String,
+    /// Secret used to sign the payload with HMAC-SHA256. The signature is sent in the
+    /// `X-FreshMeet-Signature` header
+    pub secret: Option<String>,
+}
+
+/// A saved search as returned to users. Webhook secrets are never returned
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
+pub struct SavedSearchResponse {
+    /// ID of this saved search
+    pub id: u64,
+    /// User this search belongs to
+    pub user: String,
+    /// Name of this search
+    pub name: String,
+    /// Webhook to notify when new events are found by this search
+    pub webhook: Option<WebhookResponse>,
+    /// Parameters of the search
+    #[serde(flatten)]
+    pub search: SearchRequestBody,
+}
+
+/// A webhook as returned to users
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, PartialEq)]
+pub struct WebhookResponse {
+    /// URL to post new events to
+    pub url: String,
+    /// Whether payloads are signed with a secret
+    pub has_secret: bool,
+}
+
+impl From<SavedSearch> for SavedSearchResponse {
+    fn from(saved_search: SavedSearch) -> Self {
+        return Self {
+            id: saved_search.id,
+            user: saved_search.user,
+            name: saved_search.name,
+            webhook: saved_search.webhook.map(|webhook| WebhookResponse {
+                url: webhook.url,
+                has_secret: webhook.secret.is_some(),
+            }),
+            search: saved_search.search,
+        };
+    }
+}
+
+/// Body for creating or updating a saved search
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
+pub struct SavedSearchBody {
+    /// Name of this search
+    pub name: String,
+    /// Webhook to notify when new events are found by this search
+    pub webhook: Option<Webhook>,
+    /// Parameters of the search
+    #[serde(flatten)]
+    pub search: SearchRequestBody,
+}
+
+impl SavedSearchBody {
+    /// Check the saved search is valid, returning a description of the problem if not
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("`name` must not be empty".to_string());
+        }
+        if self
+            .search
+            .query
+            .as_deref()
+            .unwrap_or_default()
+            .trim()
+            .is_empty()
+        {
+            return Err("`query` must not be empty".to_string());
+        }
+        if let Some(webhook) = &self.webhook {
+            webhook.validate()?;
+        }
+        return self.search.validate();
+    }
+}
+
+impl Webhook {
+    /// Check the webhook URL is a public `https` URL, returning a description of the problem if
+    /// not. Webhooks are requested by the server, so they must not reach into private networks
+    pub fn validate(&self) -> Result<(), String> {
+        let Ok(url) = Url::parse(&self.url) else {
+            return Err(format!("`webhook.url` `{}` is not a valid URL", self.url));
+        };
+        if url.scheme() != "https" {
+            return Err(format!("`webhook.url` `{}` must use https", self.url));
+        }
+        let host = url
+            .host_str()
+            .unwrap_or_default()
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .trim_end_matches('.')
+            .to_lowercase();
+        let is_private = match host.parse::<IpAddr>() {
+            Ok(ip) => is_private_ip(ip),
+            Err(_) => host.is_empty() || host == "localhost" || host.ends_with(".localhost"),
+        };
+        if is_private {
+            return Err(format!(
+                "`webhook.url` `{}` must not point to a private address",
+                self.url
+            ));
+        }
+        return Ok(());
+    }
+}
+
+/// Whether `ip` is a loopback, private, link local or unspecified address
+pub fn is_private_ip(ip: IpAddr) -> bool {
+    let is_private_v4 = |ip: Ipv4Addr| {
+        return ip.is_loopback() || ip.is_private() || ip.is_link_local() || ip.is_unspecified();
+    };
+    let is_private_v6 = |ip: Ipv6Addr| {
+        return ip.is_loopback()
+            || ip.is_unspecified()
+            || ip.is_unique_local()
+            || ip.is_unicast_link_local()
+            || ip.to_ipv4_mapped().is_some_and(is_private_v4);
+    };
+    return match ip {
+        IpAddr::V4(ip) => is_private_v4(ip),
+        IpAddr::V6(ip) => is_private_v6(ip),
+    };
+}
+
+impl SavedSearch {
+    /// Check the saved search is valid, returning a description of the problem if not
+    pub fn validate(&self) -> Result<(), String> {
+        return SavedSearchBody {
+            name: self.name.clone(),
+            webhook: self.webhook.clone(),
+            search: self.search.clone(),
+        }
+        .validate()
+        .map_err(|err| format!("Saved search {id} is invalid: {err}", id = self.id));
+    }
+}
+
+/// Token `user` sends as `Authorization: Bearer <token>` to use their saved searches. Derived from
+/// `secret` with HMAC-SHA256, so tokens don't need to be stored, and are handed out by whoever
+/// knows the secret
+pub fn user_token(secret: &str, user: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(user.as_bytes());
+    return hex::encode(mac.finalize().into_bytes());
+}
+
+/// Maximum number of seen event IDs remembered per saved search
+const MAX_SEEN_EVENTS: usize = 1000;
+
+/// Events a saved search has already found
+#[derive(Serialize, Deserialize, Clone)]
+struct SeenEvents {
+    /// ID of the saved search
+    search_id: u64,
+    /// IDs of events found, oldest first
+    event_ids: Vec<String>,
+}
+
+/// Layout of the saved searches file
+#[derive(Serialize, Deserialize, Default, Clone)]
+struct SavedSearchFile {
+    #[serde(default)]
+    searches: Vec<SavedSearch>,
+    #[serde(default)]
+    seen_events: Vec<SeenEvents>,
+}
+
+/// Stores saved searches of all users, writing every change to disk
+pub struct SavedSearchStore {
+    path: PathBuf,
+    file: RwLock<SavedSearchFile>,
+}
+
+impl SavedSearchStore {
+    /// Load saved searches from the file at `path`. If the file does not exist, start with no saved
+    /// searches
+    pub fn load(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let file = match std::fs::read_to_string(&path) {
+            Ok(contents) => toml::from_str::<SavedSearchFile>(&contents).map_err(|e| {
+                anyhow!(
+                    "Failed to parse saved searches in {}: {}",
+                    path.display(),
+                    e
+                )
+            })?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => SavedSearchFile::default(),
+            Err(e) => {
+                return Err(anyhow!(
+                    "Failed to read saved searches in {}: {}",
+                    path.display(),
+                    e
+                ))
+            }
+        };
+        info!(
+            "Loaded {} saved searches from {}",
+            file.searches.len(),
+            path.display()
+        );
+        return Ok(Self {
+            path,
+            file: RwLock::new(file),
+        });
+    }
+
+    /// Get all saved searches of `user`
+    pub async fn list(&self, user: &str) -> Vec<SavedSearch> {
+        return self
+            .file
+            .read()
+            .await
+            .searches
+            .iter()
+            .filter(|s| s.user == user)
+            .cloned()
+            .collect();
+    }
+
+    /// Get the saved search `id` of `user`
+    pub async fn get(&self, user: &str, id: u64) -> Option<SavedSearch> {
+        return self
+            .file
+            .read()
+            .await
+            .searches
+            .iter()
+            .find(|s| s.user == user && s.id == id)
+            .cloned();
+    }
+
+    /// Get all saved searches that have a webhook
+    pub async fn with_webhooks(&self) -> Vec<SavedSearch> {
+        return self
+            .file
+            .read()
+            .await
+            .searches
+            .iter()
+            .filter(|s| s.webhook.is_some())
+            .cloned()
+            .collect();
+    }
+
+    /// Save a new search for `user`
+    pub async fn create(&self, user: &str, body: SavedSearchBody) -> Result<SavedSearch> {
+        let mut file = self.file.write().await;
+        let saved_search = SavedSearch {
+            id: file.searches.iter().map(|s| s.id).max().unwrap_or_default() + 1,
+            user: user.to_string(),
+            name: body.name,
+            webhook: body.webhook,
+            search: body.search,
+        };
+        file.searches.push(saved_search.clone());
+        self.persist(&file).await?;
+        return Ok(saved_search);
+    }
+
+    /// Replace the saved search `id` of `user`. Returns `None` if the search does not exist
+    pub async fn update(
+        &self,
+        user: &str,
+        id: u64,
+        body: SavedSearchBody,
+    ) -> Result<Option<SavedSearch>> {
+        let mut file = self.file.write().await;
+        let Some(saved_search) = file
+            .searches
+            .iter_mut()
+            .find(|s| s.user == user && s.id == id)
+        else {
+            return Ok(None);
+        };
+        saved_search.name = body.name;
+        saved_search.webhook = body.webhook;
+        saved_search.search = body.search;
+        let saved_search = saved_search.clone();
+        // The search could return completely different events now, start over
+        file.seen_events.retain(|s| s.search_id != id);
+        self.persist(&file).await?;
+        return Ok(Some(saved_search));
+    }
+
+    /// Delete the saved search `id` of `user`. Returns `false` if the search does not exist
+    pub async fn delete(&self, user: &str, id: u64) -> Result<bool> {
+        let mut file = self.file.write().await;
+        let count = file.searches.len();
+        file.searches.retain(|s| !(s.user == user && s.id == id));
+        if file.searches.len() == count {
+            return Ok(false);
+        }
+        file.seen_events.retain(|s| s.search_id != id);
+        self.persist(&file).await?;
+        return Ok(true);
+    }
+
+    /// Get the events in `event_ids` that have not been found by saved search `id` before.
+    /// Returns `None` if the saved search has never found any events
+    pub async fn unseen_events(&self, id: u64, event_ids: &[String]) -> Option<Vec<String>> {
+        let file = self.file.read().await;
+        let seen: HashSet<&String> = file
+            .seen_events
+            .iter()
+            .find(|s| s.search_id == id)?
+            .event_ids
+            .iter()
+            .collect();
+        return Some(
+            event_ids
+                .iter()
+                .filter(|id| !seen.contains(id))
+                .cloned()
+                .collect(),
+        );
+    }
+
+    /// Remember `event_ids` as found by saved search `id`
+    pub async fn mark_seen(&self, id: u64, event_ids: Vec<String>) -> Result<()> {
+        let mut file = self.file.write().await;
+        let index = match file.seen_events.iter().position(|s| s.search_id == id) {
+            Some(index) => index,
+            None => {
+                file.seen_events.push(SeenEvents {
+                    search_id: id,
+                    event_ids: vec![],
+                });
+                file.seen_events.len() - 1
+            }
+        };
+        let seen = &mut file.seen_events[index].event_ids;
+        for event_id in event_ids {
+            if !seen.contains(&event_id) {
+                seen.push(event_id);
+            }
+        }
+        if seen.len() > MAX_SEEN_EVENTS {
+            seen.drain(..seen.len() - MAX_SEEN_EVENTS);
+        }
+        self.persist(&file).await?;
+        return Ok(());
+    }
+
+    /// Write `file` to disk
+    async fn persist(&self, file: &SavedSearchFile) -> Result<()> {
+        let contents = toml::to_string(file)?;
+        // Write to a temporary file first, so a failed write doesn't corrupt existing searches
+        let tmp_path = self.path.with_extension("toml.tmp");
+        tokio::fs::write(&tmp_path, contents).await?;
+        tokio::fs::rename(&tmp_path, &self.path).await?;
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::meetup::query::request::gql2::SeriesMode;
+
+    #[tokio::test]
+    /// Saved searches are written to disk, and can be loaded again
+    async fn can_persist_saved_searches() {
+        let path = std::env::temp_dir().join(format!(
+            "fresh_meet_saved_searches_{}.toml",
+            std::process::id()
+        ));
+        let store = SavedSearchStore::load(&path).unwrap();
+        let body = SavedSearchBody {
+            name: "Rust meetups".into(),
+            webhook: Some(Webhook {
+                url: "https://hooks.slack.com/services/T000/B000/XXXX".into(),
+                secret: Some("secret".into()),
+            }),
+            search: SearchRequestBody {
+                query: Some("rust".into()),
+                start_date: None,
+                end_date: None,
+                date_range: None,
+                when: None,
+                timezone: None,
+                per_page: Some(20),
+                lat: None,
+                lon: None,
+                city: Some("Toronto".into()),
+                series_mode: Some(SeriesMode::Collapse),
+                series_events: None,
+                dedup: None,
+                after: None,
+                from_lat: None,
+                from_lon: None,
+                max_distance_km: None,
+                sort_by_distance: None,
+                travel_modes: None,
+                sort_field: None,
+                event_type: None,
+                category_id: None,
+                days_of_week: None,
+                time_from: None,
+                time_to: None,
+            },
+        };
+        assert!(body.validate().is_ok());
+        let created = store.create("alice", body.clone()).await.unwrap();
+        store.create("bob", body).await.unwrap();
+
+        let store = SavedSearchStore::load(&path).unwrap();
+        let searches = store.list("alice").await;
+        assert_eq!(searches.len(), 1);
+        assert_eq!(searches[0].id, created.id);
+        assert_eq!(searches[0].search.query.as_deref(), Some("rust"));
+        assert_eq!(searches[0].search.series_mode, Some(SeriesMode::Collapse));
+        assert_eq!(searches[0].webhook, created.webhook);
+
+        assert!(store
+            .unseen_events(created.id, &["1".into()])
+            .await
+            .is_none());
+        store.mark_seen(created.id, vec!["1".into()]).await.unwrap();
+        let unseen = store
+            .unseen_events(created.id, &["1".into(), "2".into()])
+            .await;
+        assert_eq!(unseen, Some(vec!["2".to_string()]));
+
+        assert!(store.delete("alice", created.id).await.unwrap());
+        assert!(!store.delete("alice", created.id).await.unwrap());
+        assert!(store.get("alice", created.id).await.is_none());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    /// Webhooks must be public https URLs
+    fn can_validate_webhook() {
+        let validate = |url: &str| {
+            return Webhook {
+                url: url.into(),
+                secret: None,
+            }
+            .validate()
+            .is_ok();
+        };
+        assert!(validate("https://hooks.slack.com/services/T000/B000/XXXX"));
+        assert!(validate("https://93.184.215.14/hook"));
+        assert!(!validate("http://hooks.slack.com/services/T000/B000/XXXX"));
+        assert!(!validate("https://localhost:8080/hook"));
+        assert!(!validate("https://api.localhost/hook"));
+        assert!(!validate("https://127.0.0.1/hook"));
+        assert!(!validate("https://10.0.0.1/hook"));
+        assert!(!validate("https://192.168.1.1/hook"));
+        assert!(!validate("https://169.254.169.254/latest/meta-data"));
+        assert!(!validate("https://[::1]/hook"));
+        assert!(!validate("https://[fe80::1]/hook"));
+        assert!(!validate("https://[::ffff:127.0.0.1]/hook"));
+        assert!(!validate("not a url"));
+    }
+}
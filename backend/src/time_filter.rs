@@ -0,0 +1,154 @@
+//! Filter events by the day of the week and time of day they start at, ie weeknights after 6pm
+
+use crate::date_parser::weekday;
+use crate::date_range::parse_date;
+use crate::routes::meetup::ParamCase;
+use chrono::{Datelike, NaiveTime, Weekday};
+use chrono_tz::Tz;
+
+/// Days of the week and times of day events can start at. Times are in the time zone of the
+/// group hosting an event, so the same filter works for events anywhere
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TimeFilter {
+    /// Days events can start on. Every day if empty
+    pub days_of_week: Vec<Weekday>,
+    /// Earliest time events can start at
+    pub time_from: Option<NaiveTime>,
+    /// Latest time events can start at. Wraps past midnight if earlier than `time_from`
+    pub time_to: Option<NaiveTime>,
+}
+
+impl TimeFilter {
+    /// Build a filter from request parameters, returning a description of the problem if they
+    /// are not valid. Days are names like `monday` or `mon`, or `weekdays` and `weekends`.
+    /// Times are like `18:30`. Errors name parameters in `case`
+    pub(crate) fn parse(
+        days_of_week: &[&str],
+        time_from: Option<&str>,
+        time_to: Option<&str>,
+        case: ParamCase,
+    ) -> Result<Self, String> {
+        let mut days = vec![];
+        for day in days_of_week {
+            let day = day.trim().to_lowercase();
+            let matched = match day.as_str() {
+                "weekdays" | "weekday" => {
+                    vec![
+                        Weekday::Mon,
+                        Weekday::Tue,
+                        Weekday::Wed,
+                        Weekday::Thu,
+                        Weekday::Fri,
+                    ]
+                }
+                "weekends" | "weekend" => vec![Weekday::Sat, Weekday::Sun],
+                day => vec![weekday(day).ok_or(format!("`{day}` is not a day of the week"))?],
+            };
+            for day in matched {
+                if !days.contains(&day) {
+                    days.push(day);
+                }
+            }
+        }
+        return Ok(Self {
+            days_of_week: days,
+            time_from: time_from
+                .map(|t| parse_time(&case.name("time_from"), t))
+                .transpose()?,
+            time_to: time_to
+                .map(|t| parse_time(&case.name("time_to"), t))
+                .transpose()?,
+        });
+    }
+
+    /// Whether this filter lets every event through
+    pub fn is_empty(&self) -> bool {
+        return self.days_of_week.is_empty() && self.time_from.is_none() && self.time_to.is_none();
+    }
+
+    /// Whether an event starting at `date_time`, hosted by a group in `timezone`, passes this
+    /// filter. Events with a start time that can't be read never pass
+    pub fn matches(&self, date_time: &str, timezone: &str) -> bool {
+        let Some(start) = parse_date(date_time) else {
+            return false;
+        };
+        let start = match timezone.parse::<Tz>() {
+            Ok(tz) => start.with_timezone(&tz).naive_local(),
+            // Meetup gives start times in the offset of the event, so it is close enough
+            Err(_) => start.naive_local(),
+        };
+        if !self.days_of_week.is_empty() && !self.days_of_week.contains(&start.weekday()) {
+            return false;
+        }
+        let time = start.time();
+        return match (self.time_from, self.time_to) {
+            (Some(from), Some(to)) if to < from => time >= from || time <= to,
+            (from, to) => from.is_none_or(|from| time >= from) && to.is_none_or(|to| time <= to),
+        };
+    }
+}
+
+/// Parse the time of day in the parameter `name`
+fn parse_time(name: &str, time: &str) -> Result<NaiveTime, String> {
+    return NaiveTime::parse_from_str(time.trim(), "%H:%M")
+        .map_err(|_| format!("`{name}` `{time}` is not a valid time, ie `18:30`"));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    /// Events are matched by their start time in the time zone of their group
+    fn can_match_events() {
+        let weeknights =
+            TimeFilter::parse(&["weekdays"], Some("18:00"), None, ParamCase::Snake).unwrap();
+        let cases = [
+            // Wednesday evening in Toronto
+            ("2024-11-20T18:30:00-05:00", "America/Toronto", true),
+            ("2024-11-20T17:59:00-05:00", "America/Toronto", false),
+            // Saturday evening
+            ("2024-11-23T19:00:00-05:00", "America/Toronto", false),
+            // Friday 11pm in Vancouver is already Saturday in UTC
+            ("2024-11-23T07:00:00Z", "America/Vancouver", true),
+            // Unknown time zones use the offset of the start time
+            ("2024-11-20T19:00:00-05:00", "Nowhere/Special", true),
+            ("tomorrow", "America/Toronto", false),
+        ];
+        for (date_time, timezone, matches) in cases {
+            assert_eq!(
+                weeknights.matches(date_time, timezone),
+                matches,
+                "Matching {date_time} in {timezone}"
+            );
+        }
+
+        let late = TimeFilter::parse(&[], Some("22:00"), Some("02:00"), ParamCase::Snake).unwrap();
+        assert!(late.matches("2024-11-21T01:30:00-05:00", "America/Toronto"));
+        assert!(!late.matches("2024-11-21T12:00:00-05:00", "America/Toronto"));
+    }
+
+    #[test]
+    /// Invalid days and times are rejected
+    fn can_parse_filter() {
+        assert_eq!(
+            TimeFilter::parse(&["Sat", "weekends"], None, None, ParamCase::Snake)
+                .unwrap()
+                .days_of_week,
+            vec![Weekday::Sat, Weekday::Sun]
+        );
+        assert_eq!(
+            TimeFilter::parse(&["caturday"], None, None, ParamCase::Snake),
+            Err("`caturday` is not a day of the week".to_string())
+        );
+        assert_eq!(
+            TimeFilter::parse(&[], Some("6pm"), None, ParamCase::Snake),
+            Err("`time_from` `6pm` is not a valid time, ie `18:30`".to_string())
+        );
+        assert_eq!(
+            TimeFilter::parse(&[], None, Some("9pm"), ParamCase::Camel),
+            Err("`timeTo` `9pm` is not a valid time, ie `18:30`".to_string())
+        );
+        assert!(TimeFilter::default().is_empty());
+    }
+}
@@ -1,5 +1,11 @@
 use chrono::{DateTime, FixedOffset, Utc};
+use markdown::{mdast, to_mdast, ParseOptions};
+use serde::{de::Error, Deserialize, Deserializer};
+use std::fmt::Display;
+use std::str::FromStr;
 use tracing::debug;
+use utoipa::openapi::schema::{AnyOfBuilder, ObjectBuilder, Schema, Type};
+use utoipa::openapi::RefOr;
 
 /// Get the current time stamp in EST
 ///
@@ -34,3 +40,197 @@ pub fn eod() -> String {
     debug!("End of date time: {est_eod}");
     return est_eod;
 }
+
+/// Mean radius of the earth in kilometres
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+/// Great circle distance in kilometres between two `(lat, lon)` points, using the haversine
+/// formula
+pub fn haversine_km(from: (f64, f64), to: (f64, f64)) -> f64 {
+    let (lat1, lon1) = (from.0.to_radians(), from.1.to_radians());
+    let (lat2, lon2) = (to.0.to_radians(), to.1.to_radians());
+    let a = ((lat2 - lat1) / 2.0).sin().powi(2)
+        + lat1.cos() * lat2.cos() * ((lon2 - lon1) / 2.0).sin().powi(2);
+    return 2.0 * EARTH_RADIUS_KM * a.sqrt().asin();
+}
+
+/// Convert markdown to plain text, dropping all formatting
+pub fn markdown_to_text(markdown: &str) -> String {
+    let Ok(root) = to_mdast(markdown, &ParseOptions::default()) else {
+        return markdown.to_string();
+    };
+    let mut text = String::new();
+    push_text(&root, &mut text);
+
+    // Blocks are separated by at most one empty line
+    let mut collapsed = String::with_capacity(text.len());
+    for line in text.lines().map(str::trim_end) {
+        if line.is_empty() && (collapsed.is_empty() || collapsed.ends_with("\n\n")) {
+            continue;
+        }
+        collapsed.push_str(line);
+        collapsed.push('\n');
+    }
+    return collapsed.trim_end().to_string();
+}
+
+/// Shorten `text` to at most `max_chars` characters, on a single line.
+///
+/// Text is cut at the end of the last sentence that fits. If not even the first sentence fits,
+/// it's cut at a word instead, and an ellipsis is added
+pub fn summarize(text: &str, max_chars: usize) -> String {
+    let text = text.split_whitespace().collect::<Vec<&str>>().join(" ");
+    if text.chars().count() <= max_chars {
+        return text;
+    }
+    let cut = text
+        .char_indices()
+        .nth(max_chars)
+        .map_or(text.len(), |(i, _)| i);
+    let head = &text[..cut];
+    // A sentence ends with punctuation followed by a space, or the cut itself
+    let sentence_end = head
+        .char_indices()
+        .filter(|(i, c)| matches!(c, '.' | '!' | '?') && text[i + c.len_utf8()..].starts_with(' '))
+        .map(|(i, c)| i + c.len_utf8())
+        .next_back();
+    if let Some(end) = sentence_end {
+        return head[..end].to_string();
+    }
+    let word_end = head.rfind(' ').unwrap_or(head.len());
+    return format!("{}…", head[..word_end].trim_end());
+}
+
+/// Schema of a string enum that also accepts values other than `known`, for enums with an
+/// `Other(String)` variant
+pub fn open_enum_schema(known: &[&str]) -> RefOr<Schema> {
+    return AnyOfBuilder::new()
+        .item(
+            ObjectBuilder::new()
+                .schema_type(Type::String)
+                .enum_values(Some(known.iter().copied())),
+        )
+        .item(ObjectBuilder::new().schema_type(Type::String))
+        .into();
+}
+
+/// Deserialize a list given either as a list, or as a comma separated string, ie `sat,sun`.
+/// Query strings can only send the string form
+pub fn deserialize_list<'de, D, T>(deserializer: D) -> Result<Option<Vec<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + FromStr,
+    T::Err: Display,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum List<T> {
+        Items(Vec<T>),
+        Text(String),
+    }
+
+    return match Option::<List<T>>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(List::Items(items)) => Ok(Some(items)),
+        Some(List::Text(text)) => text
+            .split(',')
+            .map(|item| item.trim().parse().map_err(D::Error::custom))
+            .collect::<Result<_, _>>()
+            .map(Some),
+    };
+}
+
+/// Compare `a` and `b` in constant time, so tokens can't be guessed by timing responses
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    return a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0;
+}
+
+/// Append the text in `node` and its children to `text`
+fn push_text(node: &mdast::Node, text: &mut String) {
+    match node {
+        mdast::Node::Text(t) => text.push_str(&t.value),
+        mdast::Node::InlineCode(c) => text.push_str(&c.value),
+        mdast::Node::Code(c) => {
+            text.push_str(&c.value);
+            text.push_str("\n\n");
+        }
+        mdast::Node::Break(_) => text.push('\n'),
+        mdast::Node::Html(_) => {}
+        mdast::Node::ListItem(item) => {
+            text.push_str("- ");
+            for child in &item.children {
+                match child {
+                    // Keep list items on consecutive lines
+                    mdast::Node::Paragraph(p) => {
+                        p.children.iter().for_each(|c| push_text(c, text));
+                        text.push('\n');
+                    }
+                    _ => push_text(child, text),
+                }
+            }
+        }
+        mdast::Node::List(_) => {
+            node.children()
+                .into_iter()
+                .flatten()
+                .for_each(|c| push_text(c, text));
+            text.push('\n');
+        }
+        _ => {
+            for child in node.children().into_iter().flatten() {
+                push_text(child, text);
+            }
+            if matches!(node, mdast::Node::Paragraph(_) | mdast::Node::Heading(_)) {
+                text.push_str("\n\n");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::extract::Query;
+
+    #[test]
+    /// Summaries are cut at the last sentence that fits, or a word if no sentence fits
+    fn can_summarize() {
+        let text = "Rust meetup.  Talks about async!\nPizza after? Yes.";
+        assert_eq!(
+            summarize(text, 100),
+            "Rust meetup. Talks about async! Pizza after? Yes."
+        );
+        assert_eq!(summarize(text, 40), "Rust meetup. Talks about async!");
+        assert_eq!(summarize(text, 13), "Rust meetup.");
+        assert_eq!(summarize("Bring your own laptop", 12), "Bring your…");
+        assert_eq!(summarize("Café réunion à Montréal", 10), "Café…");
+    }
+
+    #[test]
+    /// Lists are read from lists, and from comma separated strings in query strings
+    fn can_deserialize_list() {
+        #[derive(Deserialize)]
+        struct Params {
+            #[serde(default, deserialize_with = "deserialize_list")]
+            days: Option<Vec<String>>,
+        }
+        let days = |params: Params| params.days;
+        let json = |json: serde_json::Value| days(serde_json::from_value(json).unwrap());
+        let query = |query: &str| {
+            let uri = format!("/search.atom?{query}").parse().unwrap();
+            return days(Query::try_from_uri(&uri).unwrap().0);
+        };
+
+        let weekend = Some(vec!["sat".to_string(), "sun".to_string()]);
+        assert_eq!(json(serde_json::json!({ "days": ["sat", "sun"] })), weekend);
+        assert_eq!(json(serde_json::json!({ "days": "sat, sun" })), weekend);
+        assert_eq!(json(serde_json::json!({ "days": null })), None);
+        assert_eq!(json(serde_json::json!({})), None);
+        assert_eq!(query("days=sat,sun"), weekend);
+        assert_eq!(query("days=sat"), Some(vec!["sat".to_string()]));
+        assert_eq!(query(""), None);
+    }
+}
@@ -129,7 +129,8 @@ export interface components {
             eventUrl: string;
             featuredEventPhoto?: null | components["schemas"]["FeaturedEventPhoto"];
             feeSettings?: null | components["schemas"]["FeeSettings"];
-            googleMapsUrl?: string | null;
+            /** @description Link to directions to the venue */
+            mapUrl?: string | null;
             group: components["schemas"]["Group"];
             id: string;
             isAttending: boolean;
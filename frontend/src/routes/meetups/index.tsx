@@ -76,12 +76,12 @@ export default function () {
                         <a target="_blank" href={node.node.eventUrl}>
                           {node.node.title}
                         </a>
-                        <Show when={node.node.googleMapsUrl}>
+                        <Show when={node.node.mapUrl}>
                           <hr />
                           Location:{" "}
                           <a
                             target="_blank"
-                            href={node.node.googleMapsUrl ?? ""}
+                            href={node.node.mapUrl ?? ""}
                           >
                             {node.node.venue?.name}
                           </a>